@@ -0,0 +1,31 @@
+//! Compares rolling an expression for its totals alone against collecting a full `Roll` for
+//! every roll, which `roll_totals()` claims to be considerably cheaper.
+
+#[macro_use]
+extern crate criterion;
+extern crate d20;
+
+use criterion::Criterion;
+use std::hint::black_box;
+
+const EXPRESSION: &str = "4d6kh3 + 2d8 - 1";
+const ROLLS: usize = 1000;
+
+fn totals(c: &mut Criterion) {
+    let mut group = c.benchmark_group("1000 rolls of 4d6kh3+2d8-1");
+    group.bench_function("roll_totals", |b| {
+        b.iter(|| d20::roll_totals(black_box(EXPRESSION), ROLLS).unwrap())
+    });
+    group.bench_function("roll_times", |b| {
+        b.iter(|| {
+            d20::roll_times(black_box(EXPRESSION), ROLLS)
+                .unwrap()
+                .map(|r| r.unwrap().total)
+                .collect::<Vec<i64>>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, totals);
+criterion_main!(benches);
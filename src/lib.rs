@@ -18,7 +18,14 @@
 //! * +6
 //! * -2
 //! * 3d10+5d100-21+7
+//! * d20 + 5
 //!
+//! The number of dice may be left out of a term when it is one, so `d20` is the same as `1d20`.
+//! A term may also roll no dice at all (`0d6`), in which case it adds nothing, but a die must
+//! have at least one side, so `1d0` is an error rather than a roll.
+//!
+//! Whitespace anywhere in an expression is ignored, and the `d` of a term may be written in
+//! either case, so `3 D 6 + 4` is the same roll as `3d6+4`.
 //!
 //! Roll expressions can have arbitrary length and complexity, and it is perfectly legal for the final result
 //! of a roll expression to be negative after applying modifiers.
@@ -44,14 +51,22 @@
 //! A valid `Roll` can be converted into an open ended iterator via its `into_iter()` method, providing successive
 //! rolls of the given die roll expression.
 //!
-//! _Note that it will be necessary to constrain the iterator via `take(n)`._
+//! _Note that it will be necessary to constrain the iterator via `take(n)`._ An unconstrained
+//! iterator never ends, so collecting it will hang; `roll_times()` produces an iterator that
+//! is already bounded.
+//!
+//! Each item is a `Result`, as a roll can still fail when the expression divides by a result
+//! that came up zero.
+//!
+//! An expression that will be rolled many times can also be parsed once into a `RollExpr`,
+//! which rolls it without parsing it again.
 //! 
 //! ```rust
 //! extern crate d20;
 //! use d20::*;
 //!
 //! fn main() {
-//!     let v: Vec<Roll> = d20::roll_dice("3d6").unwrap().into_iter().take(3).collect();
+//!     let v: Vec<Roll> = d20::roll_dice("3d6").unwrap().into_iter().take(3).collect::<Result<_, _>>().unwrap();
 //!
 //!     assert_eq!(v.len(), 3);
 //!     assert!(v[0].total >= 3 && v[0].total <= 18);
@@ -61,6 +76,102 @@
 //!
 //! ```
 //!
+//! ### Open-Ended Dice
+//! Adding the `open` suffix to a die roll term (e.g. `1d100open`) makes its dice _open-ended_ in
+//! both directions. A die landing in the top 5% of its faces (96-100 on a d100) is rolled again
+//! and the new roll added, repeating for as long as the top keeps coming up. A die landing in the
+//! bottom 5% (01-05) is rolled again and the new roll _subtracted_, again repeating on further
+//! high rolls. The thresholds can be given explicitly as a number of faces, so `1d20open2` opens
+//! on 1-2 and 19-20.
+//!
+//! Every roll in a chain is recorded in the `Roll` values, with subtracted rolls stored as
+//! negative numbers. A single die never adds more than `MAX_EXPLOSIONS` extra rolls.
+//!
+//! ### Exploding Dice
+//! Adding `!` to a die roll term (e.g. `3d6!`) makes its dice _explode_: a die showing its
+//! highest face is rolled again and the new roll added, repeating for as long as the highest face
+//! keeps coming up. Every roll in a chain is recorded in the `Roll` values, and a single die
+//! never adds more than `MAX_EXPLOSIONS` extra rolls. A one-sided die cannot explode.
+//!
+//! Doubling the `!` (e.g. `1d6!!`) makes the dice _compound_ instead: the rolls of a chain are
+//! added up into a single face, which can exceed the sides of the die, so a `1d6!!` roll of 6,
+//! then 6, then 2 is recorded and shown as a single die, `1d6!![14] (Total: 14)`.
+//!
+//! Following the `!` with a `p` (e.g. `1d6!p`) makes the dice _penetrate_ instead: they explode
+//! like any other dice, but every roll after the first in a chain counts one less than it shows.
+//! Each roll is still recorded, and the displayed chain shows what each roll counted as, so a
+//! `1d6!p` roll of 6, then 6, then 2 is shown as `1d6!p[6→6↓5→2↓1] (Total: 12)`.
+//!
+//! ### Dice Pools
+//! A die roll term followed by a comparison (`>=`, `>`, `<=`, `<` or `=`) and a target face is a
+//! _dice pool_: instead of adding up its dice, the term counts how many of them satisfy the
+//! comparison. For example, `6d10>=7` contributes the number of dice showing 7 or more.
+//! When a pool roll is displayed, the dice that succeeded are marked with a `*`, as in
+//! `6d10>=7[3, 9*, 7*, 2, 10*, 5] (Total: 3 successes)`.
+//! `expected_successes()` computes how many successes a pool expression produces on average.
+//!
+//! ### Rerolling Dice
+//! A die roll term followed by `r` and a number (e.g. `4d6r1`) rerolls every die showing that
+//! number or less, once, keeping the new face even if it is just as low. Any other comparison
+//! can follow `ro` instead, as in `2d6ro<3` or `1d20ro=1`. Both rolls of a rerolled die are
+//! recorded in the `Roll` values, and the discarded face is shown in parentheses when the roll
+//! is displayed, as in `4d6r1[(1), 5, 3, 4, 2]`.
+//!
+//! ### Keeping Dice
+//! A die roll term followed by `kh` and a number (e.g. `4d6kh3`) rolls all of its dice but only
+//! counts the highest of them, so `2d20kh1` rolls with advantage. Likewise `kl` keeps the lowest
+//! dice, so `2d20kl1` rolls with disadvantage. Without a number, `kh` and `kl` keep a single die.
+//! Every die rolled is still recorded in the `Roll` values, and the dice that were not kept are
+//! shown in parentheses when the roll is displayed, as in `2d20kl1[(18), 7] (Total: 7)`.
+//!
+//! ### Minimum and Maximum Results
+//! A die roll term followed by `min` and a face (e.g. `2d6min3`) counts any die that shows a
+//! lower face as showing that face instead, as for the Great Weapon Fighting style. The faces
+//! actually rolled are still recorded in the `Roll` values, and a raised die shows both faces
+//! when the roll is displayed, as in `2d6min3[1↑3, 5] (Total: 8)`.
+//!
+//! Likewise, `max` and a face (e.g. `3d8max6`) counts any die that shows a higher face as
+//! showing that face, displayed as `8↓6`. The two can be combined, as in `3d8min2max6`.
+//!
+//! Dice are raised and lowered before any are kept, so `2d6min3kh1` keeps the highest die as
+//! counted; the source of `DieRollTerm::kept` explains why the order never changes the total.
+//!
+//! The minimum and maximum apply to each die after any penalty it takes, so `min1` floors every
+//! die at 1 for effects that deal "at least 1 per die". Without it a penetrating `1d2!p` roll of
+//! 2 then 1 counts its second roll as 0, shown as `2→1↓0`; `1d2!pmin1` counts it as 1.
+//!
+//! ### Multiplication and Division
+//! Terms can be multiplied with `*` and divided with `/`, as in `1d6*10` or `2d8/2`. These bind
+//! more tightly than adding and subtracting, so `2d6+1d4*3` adds three times the 1d4 to the 2d6,
+//! and chains of them apply from left to right. Division rounds toward zero, and dividing by
+//! zero is an error.
+//!
+//! ### Parentheses
+//! Parentheses group parts of an expression, so `(1d6+1)*2` doubles the total of the 1d6 and
+//! the modifier, and `1d20-(1d4+1)` subtracts the whole group. Groups can be nested, and a
+//! parenthesis that is never closed (or never opened) is an error.
+//!
+//! ### Percentile Dice
+//! A die roll term may be written with `%` or `00` in place of the number of sides, so `1d%` and
+//! `1d00` both roll a d100. Percentile dice are always displayed as `d100`, so `2d%` is shown
+//! as `2d100`.
+//!
+//! ### Fudge Dice
+//! The _Fudge dice_ of Fate are written with an `F` in place of the number of sides (e.g. `4dF`).
+//! Each die shows -1, 0 or +1 with equal chance, so `4dF` totals between -4 and +4. Their faces
+//! are shown as `+`, `0` and `-` when the roll is displayed, as in `4dF[+, 0, -, +] (Total: 1)`.
+//!
+//! ### Custom Dice
+//! A die with faces of its own is written with the faces listed in brackets in place of the
+//! number of sides, as in `2d[2,4,6,8]` or `1d[-1,0,0,1]`. Each face is equally likely to come
+//! up, so listing a face more than once makes it more likely. Custom dice are summed like any
+//! other dice, but cannot take suffixes such as `!` or `kh`.
+//!
+//! ### Labels
+//! An expression may end with a label in brackets saying what the roll is for, as in
+//! `1d8+3 [fire damage]`. The label is kept in the `label` of the `Roll`, spacing and all, and
+//! shown when the roll is displayed: `1d8[5]+3 [fire damage] (Total: 8)`.
+//!
 //! ### Range Rolls
 //! If you are less concerned about dice rolls and require only a random number within a given range, `roll_range()`
 //! will do just that.
@@ -73,13 +184,76 @@
 //! # }
 //! ```
 //!
+//! ### Serialization
+//! Enabling the optional `serde` feature derives `Serialize` and `Deserialize` for `Roll`,
+//! `RollExpr` and the types they are made of, so rolls can be stored and read back later.
+//! `RollError` can be serialized but not deserialized.
+//!
+//! ### `no_std`
+//! The `std` feature is enabled by default. Without it the crate only needs `core` and `alloc`,
+//! for use on devices with no operating system. Expressions can still be parsed, built with
+//! `RollBuilder` and rolled with `RollExpr::roll_with()` from a random number generator of the
+//! caller's choosing, but everything that rolls from the thread-local generator, needs floating
+//! point functions from `std` or keeps `metadata` in a `HashMap` is left out.
+//!
+//! Die roll terms are matched by a hand-written scanner. The optional `regex` feature matches
+//! them with a regular expression instead, accepting exactly the same terms; it needs `std`.
+//!
+//! ### WebAssembly
+//! The `wasm` feature exports `roll_js()` through `wasm-bindgen`, so browser code can call
+//! `roll_js("3d6 + 4")` and get back the roll as a plain object, or catch the error message as
+//! a string. It turns on the `serde` feature.
+//!
 //! 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
 extern crate rand;
+#[cfg(feature = "regex")]
 extern crate regex;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+#[cfg(feature = "wasm")]
+extern crate serde_wasm_bindgen;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
 
-use std::fmt;
-use rand::{thread_rng, Rng};
-use regex::Regex;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap;
+#[cfg(not(feature = "std"))]
+use alloc::string::{String, ToString};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::cmp;
+use core::error::Error;
+use core::fmt;
+#[cfg(feature = "std")]
+use core::iter;
+#[cfg(feature = "std")]
+use core::ops::{Deref, RangeInclusive};
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "std")]
+use std::vec;
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::{Isaac64Rng, Rng, SeedableRng};
+
+mod parser;
+use parser::{parse_input, parse_terms, strip_whitespace, term_len};
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "wasm")]
+pub use wasm::roll_js;
 
 
 
@@ -97,14 +271,456 @@ use regex::Regex;
 /// The `total` field contains the net result of evaluating the entire roll expression.
 ///
 /// You can evaluate a roll expression (perform a roll) mutliple times by converting it into an iterator.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Roll {
     /// A die roll expression conforming to the format specification
     pub drex: String,
+    /// The expression as it was entered, keeping its original spacing (e.g. `2d6 + 5`), while
+    /// `drex` has every whitespace character removed (`2d6+5`)
+    pub raw: String,
+    /// The label given after the expression to say what the roll is for, as in
+    /// `1d8+3 [fire damage]`, without its brackets
+    pub label: Option<String>,
     /// The results of evaluating each term in the expression
-    pub values: Vec<(DieRollTerm, Vec<i8>)>,
+    pub values: Vec<(DieRollTerm, Vec<i32>)>,
     /// The net final result of evaluating all terms in the expression
-    pub total: i32,
+    pub total: i64,
+    /// Arbitrary context attached to the roll, such as who rolled it and what for. Metadata
+    /// never affects evaluation or display, and is ignored when comparing or hashing rolls.
+    #[cfg(feature = "std")]
+    pub metadata: HashMap<String, String>,
+    /// How the results of the terms combine into the total
+    expr: Expr,
+}
+
+impl Roll {
+    /// Returns the net contribution of the dice in this roll, leaving out every flat modifier.
+    /// Subtracted dice terms (e.g. `-1d4`) count against the result just as they do in `total`.
+    /// The results of the dice terms are added up, even if the expression multiplies or divides
+    /// them or subtracts a parenthesized group holding them.
+    pub fn dice_only_total(&self) -> i64 {
+        self.dice_values()
+            .fold(0i64, |sum, v| sum + DieRollTerm::calculate(v))
+    }
+
+    /// Returns the net contribution of the dice in this roll, the same as `dice_only_total()`.
+    ///
+    /// ```rust
+    /// # extern crate d20;
+    /// # fn main() {
+    ///     let r = d20::roll_dice("3d1 + 5 - 1d1").unwrap();
+    ///     assert_eq!(r.dice_total(), 2);
+    ///     assert_eq!(r.dice_total() + r.modifier_total(), r.total);
+    /// # }
+    /// ```
+    pub fn dice_total(&self) -> i64 {
+        self.dice_only_total()
+    }
+
+    /// Returns the net contribution of the flat modifiers in this roll, leaving out every dice
+    /// term. For a flat sum without parenthesized groups, multiplication or division,
+    /// `dice_only_total()` and `modifier_total()` add up to `total`, splitting a `3d1+5` into 3
+    /// and 5. In `10-(1d1+2)` they are 1 and 12 while the total is 7.
+    pub fn modifier_total(&self) -> i64 {
+        self.values
+            .iter()
+            .filter(|v| matches!(v.0, DieRollTerm::Modifier(_)))
+            .fold(0i64, |sum, v| sum + DieRollTerm::calculate(v))
+    }
+
+    /// Returns the natural result of the roll: the sum of every die face as it was rolled, before
+    /// any flat modifiers are applied. For a `1d20+5` that rolled a 15, `natural_total()` is 15
+    /// while `total` is 20.
+    ///
+    /// Unlike `dice_only_total()`, faces from subtracted dice terms are summed as rolled rather
+    /// than counted against the result.
+    pub fn natural_total(&self) -> i64 {
+        self.dice_values()
+            .flat_map(|v| v.0.faces(&v.1))
+            .map(i64::from)
+            .sum()
+    }
+
+    /// Returns the margin by which a dice pool roll met or missed the `required` number of
+    /// successes: positive when it scored more successes than required, zero when it scored
+    /// exactly enough and negative when it fell short by that many.
+    ///
+    /// The successes of a pool roll are its `total`, so modifiers count as automatic successes
+    /// (or failures). Returns `None` for rolls that are not dice pools, i.e. that have no pool
+    /// term (`6d10>=7`) or that also sum the faces of any ordinary die roll term.
+    pub fn success_margin(&self, required: usize) -> Option<i64> {
+        let mut pools = self.values.iter().filter_map(|v| match v.0 {
+            DieRollTerm::DieRoll { success, .. } => Some(success.is_some()),
+            DieRollTerm::Fudge { .. } | DieRollTerm::Custom { .. } => Some(false),
+            DieRollTerm::Modifier(_) => None,
+        });
+        let first = pools.next();
+        if first == Some(true) && pools.all(|is_pool| is_pool) {
+            Some(self.total - required as i64)
+        } else {
+            None
+        }
+    }
+
+    /// Grants advantage after the fact to a roll of a single d20 plus modifiers (e.g. `1d20+5`):
+    /// a second d20 is rolled and the higher of the two faces kept, with the total updated to
+    /// match. The discarded face is not recorded. Rolls with any other dice are an error.
+    #[cfg(feature = "std")]
+    pub fn with_advantage(&self) -> Result<Roll, RollError> {
+        self.reroll_d20(cmp::max)
+    }
+
+    /// Imposes disadvantage after the fact on a roll of a single d20 plus modifiers: a second
+    /// d20 is rolled and the lower of the two faces kept. See `with_advantage()`.
+    #[cfg(feature = "std")]
+    pub fn with_disadvantage(&self) -> Result<Roll, RollError> {
+        self.reroll_d20(cmp::min)
+    }
+
+    /// Rolls a second d20 for a single-d20 roll and keeps the face chosen by `pick`.
+    #[cfg(feature = "std")]
+    fn reroll_d20(&self, pick: fn(i32, i32) -> i32) -> Result<Roll, RollError> {
+        let old = self.natural_d20().ok_or(RollError::Unsupported("expected exactly one d20"))?;
+        let new = pick(old, thread_rng().gen_range(1, 21));
+
+        let values: Vec<_> = self.values
+            .iter()
+            .map(|v| match v.0 {
+                DieRollTerm::DieRoll { .. } => (v.0.clone(), vec![new]),
+                DieRollTerm::Fudge { .. } | DieRollTerm::Custom { .. } | DieRollTerm::Modifier(_) => v.clone(),
+            })
+            .collect();
+        Ok(Roll {
+            drex: self.drex.clone(),
+            raw: self.raw.clone(),
+            label: self.label.clone(),
+            total: self.expr.evaluate(&subtotals(&values))?,
+            values,
+            #[cfg(feature = "std")]
+            metadata: self.metadata.clone(),
+            expr: self.expr.clone(),
+        })
+    }
+
+    /// Spends a fate point on this roll: the lowest die is turned to its maximum face and the
+    /// total recomputed. When several dice tie for lowest, the first of them in the expression
+    /// is turned. Exploding and open-ended dice are left alone, as their maximum face would
+    /// explode, as are dice that were not kept; a roll with no other dice is returned unchanged,
+    /// as is a roll where turning the die would leave the expression dividing by zero.
+    pub fn spend_fate_on_lowest(&self) -> Roll {
+        let mut values = self.values.clone();
+
+        // (term index, value index, face) of the lowest face that counts towards the total
+        let mut lowest: Option<(usize, usize, i32)> = None;
+        for (i, v) in values.iter().enumerate() {
+            if let DieRollTerm::Modifier(_) = v.0 {
+                continue;
+            }
+            if v.0.explodes() {
+                continue;
+            }
+            let chains = v.0.chains(&v.1);
+            let kept = v.0.kept(&chains);
+            let mut end = 0;
+            for (chain, k) in chains.into_iter().zip(kept) {
+                end += chain.len();
+                let face = chain[chain.len() - 1];
+                if k && lowest.is_none_or(|(_, _, low)| face < low) {
+                    lowest = Some((i, end - 1, face));
+                }
+            }
+        }
+
+        if let Some((i, j, _)) = lowest {
+            values[i].1[j] = match values[i].0 {
+                DieRollTerm::DieRoll { sides, .. } => sides as i32,
+                DieRollTerm::Custom { ref faces, .. } => faces.iter().cloned().max().unwrap_or(0),
+                DieRollTerm::Fudge { .. } | DieRollTerm::Modifier(_) => 1,
+            };
+        }
+        let total = match self.expr.evaluate(&subtotals(&values)) {
+            Ok(total) => total,
+            Err(_) => {
+                values = self.values.clone();
+                self.total
+            }
+        };
+        Roll {
+            drex: self.drex.clone(),
+            raw: self.raw.clone(),
+            label: self.label.clone(),
+            values,
+            total,
+            #[cfg(feature = "std")]
+            metadata: self.metadata.clone(),
+            expr: self.expr.clone(),
+        }
+    }
+
+    /// Returns the face rolled on the d20 of a single-d20 expression such as an attack roll
+    /// (`1d20+5`), or `None` if the roll has any other dice.
+    #[cfg(feature = "std")]
+    fn natural_d20(&self) -> Option<i32> {
+        let mut dice = self.dice_values();
+        match (dice.next(), dice.next()) {
+            (Some(&(ref term @ DieRollTerm::DieRoll {
+                multiplier: 1,
+                sides: 20,
+                open_ended: None,
+                exploding: false,
+                success: None,
+                ..
+            }, ref values)), None) => Some(term.faces(values)[0]),
+            _ => None,
+        }
+    }
+
+    /// Returns the highest face rolled on any single die in this roll, or `None` if the roll
+    /// has no dice. Useful for rules that reference an individual die, such as adding the
+    /// best die again on a brutal critical.
+    pub fn best_single_die(&self) -> Option<i32> {
+        self.dice_values().flat_map(|v| v.0.faces(&v.1)).max()
+    }
+
+    /// Returns every die rolled in this roll, term by term, leaving out the modifiers. The
+    /// values are as recorded, so exploded and rerolled dice contribute every roll they made and
+    /// dice that were not kept are still included.
+    pub fn all_dice(&self) -> Vec<i32> {
+        self.dice_values().flat_map(|v| v.1.iter().cloned()).collect()
+    }
+
+    /// Returns the dice rolled for the term at `index`, as recorded, or `None` if that term is a
+    /// modifier or `index` is past the last term.
+    pub fn dice_for_term(&self, index: usize) -> Option<&[i32]> {
+        match self.values.get(index) {
+            Some((DieRollTerm::Modifier(_), _)) | None => None,
+            Some((_, values)) => Some(values),
+        }
+    }
+
+    /// Returns how much this roll's total beat (positive) or fell short of (negative) the
+    /// total of a `previous` roll.
+    pub fn delta(&self, previous: &Roll) -> i64 {
+        self.total - previous.total
+    }
+
+    /// Returns whether this roll's total is strictly greater than that of a `previous` roll.
+    pub fn improved_over(&self, previous: &Roll) -> bool {
+        self.delta(previous) > 0
+    }
+
+    /// Returns the change in each term's contribution since a `previous` roll of the same
+    /// expression, or `None` if the two rolls are of different expressions.
+    pub fn term_deltas(&self, previous: &Roll) -> Option<Vec<i64>> {
+        if self.drex != previous.drex {
+            return None;
+        }
+        Some(self.subtotals().iter().zip(previous.subtotals()).map(|(a, b)| a - b).collect())
+    }
+
+    /// Pools the faces of every die in this roll, across all of its terms, and sums the highest
+    /// `n` of them. Flat modifiers are ignored, as are faces discarded by a reroll. If `n` is at
+    /// least the number of dice, every face is summed.
+    pub fn sum_top_n(&self, n: usize) -> i64 {
+        let mut faces: Vec<i32> = self.dice_values().flat_map(|v| v.0.faces(&v.1)).collect();
+        faces.sort_by_key(|&face| cmp::Reverse(face));
+        faces.iter().take(n).map(|&face| i64::from(face)).sum()
+    }
+
+    /// Returns the signed result of each term, in the order of `values`. A subtracted `-2d6` that
+    /// rolled 3 and 4 results in -7. In a flat sum without parenthesized groups, these are the
+    /// contributions of the terms to the total. Only the sign written before each term is taken,
+    /// so `10-(1d1+2)` results in `[10, 1, 2]`, and it is the expression that combines them into
+    /// the total.
+    pub fn subtotals(&self) -> Vec<i64> {
+        subtotals(&self.values)
+    }
+
+    /// Returns each face that came up on more than one die of this roll, with how many dice
+    /// showed it, in ascending order of face. Faces discarded by a reroll are not counted.
+    pub fn matches(&self) -> Vec<(i32, usize)> {
+        let mut counts = BTreeMap::new();
+        for face in self.dice_values().flat_map(|v| v.0.faces(&v.1)) {
+            *counts.entry(face).or_insert(0) += 1;
+        }
+        counts.into_iter().filter(|&(_, n)| n > 1).collect()
+    }
+
+    /// Returns whether this roll _glitched_ in the Shadowrun sense: at least half of its dice
+    /// came up 1. Rolls without dice never glitch.
+    pub fn is_glitch(&self) -> bool {
+        let faces: Vec<i32> = self.dice_values().flat_map(|v| v.0.faces(&v.1)).collect();
+        let ones = faces.iter().filter(|&&face| face == 1).count();
+        !faces.is_empty() && ones * 2 >= faces.len()
+    }
+
+    /// Returns whether any die in this roll showed its highest face, such as a 20 on a d20. Only
+    /// dice that count towards the result are considered, so the dropped die of `2d20kh1` is
+    /// ignored, and Fudge dice never crit.
+    pub fn is_critical(&self) -> bool {
+        (0..self.values.len()).any(|i| self.term_critical(i))
+    }
+
+    /// Returns whether any die in this roll showed a 1. See `is_critical()` for which dice are
+    /// considered.
+    pub fn is_fumble(&self) -> bool {
+        (0..self.values.len()).any(|i| self.term_fumbled(i))
+    }
+
+    /// Returns whether any die of the term at `index` showed its highest face. Modifier and
+    /// Fudge terms never crit, and an `index` past the last term returns `false`.
+    pub fn term_critical(&self, index: usize) -> bool {
+        self.term_shows(index, i32::from)
+    }
+
+    /// Returns whether any die of the term at `index` showed a 1. Modifier and Fudge terms never
+    /// fumble, and an `index` past the last term returns `false`.
+    pub fn term_fumbled(&self, index: usize) -> bool {
+        self.term_shows(index, |_| 1)
+    }
+
+    /// Returns whether any counted die of the term at `index` showed the face that `face` picks
+    /// for its number of sides.
+    fn term_shows(&self, index: usize, face: fn(u16) -> i32) -> bool {
+        match self.values.get(index) {
+            Some(&(ref term @ DieRollTerm::DieRoll { sides, .. }, ref values)) => {
+                term.faces(values).contains(&face(sides))
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns how many extra dice were rolled because of explosions across the whole roll,
+    /// or 0 if no die exploded. A die whose chain reads `10→10→7` exploded twice. A compounding
+    /// die keeps only the sum of its chain, which on a d6 exploded once for every full 6 it
+    /// holds, so a 15 exploded twice.
+    pub fn explosion_count(&self) -> usize {
+        self.dice_values()
+            .filter(|v| v.0.explodes())
+            .map(|v| match v.0 {
+                DieRollTerm::DieRoll { sides, compounding: true, .. } => {
+                    v.1.iter().map(|&f| ((f - 1) / i32::from(sides)) as usize).sum::<usize>()
+                }
+                ref term => term.chains(&v.1).iter().map(|c| c.len() - 1).sum(),
+            })
+            .sum()
+    }
+
+    /// Returns whether any die of the term at `index` exploded, i.e. was exploding or
+    /// open-ended and rolled again. A compounding die exploded when it shows more than its
+    /// sides. Modifier terms never explode, and an `index` past the last term returns `false`.
+    pub fn term_exploded(&self, index: usize) -> bool {
+        match self.values.get(index) {
+            Some(&(DieRollTerm::DieRoll { sides, compounding: true, .. }, ref values)) => {
+                values.iter().any(|&f| f > i32::from(sides))
+            }
+            Some((term, values)) if term.explodes() => {
+                term.chains(values).iter().any(|c| c.len() > 1)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether any die of the term at `index` was rerolled (e.g. a 1 on `2d6ro<3`).
+    /// Modifier terms are never rerolled, and an `index` past the last term returns `false`.
+    pub fn term_rerolled(&self, index: usize) -> bool {
+        match self.values.get(index) {
+            Some(&(ref term @ DieRollTerm::DieRoll { reroll: Some(_), .. }, ref values)) => {
+                term.chains(values).iter().any(|c| c.len() > 1)
+            }
+            _ => false,
+        }
+    }
+
+    /// Iterates over the results of the die roll terms of this roll, skipping modifiers.
+    fn dice_values(&self) -> impl Iterator<Item = &(DieRollTerm, Vec<i32>)> {
+        self.values.iter().filter(|v| match v.0 {
+            DieRollTerm::DieRoll { .. } | DieRollTerm::Fudge { .. } | DieRollTerm::Custom { .. } => true,
+            DieRollTerm::Modifier(_) => false,
+        })
+    }
+
+    /// Returns a new `Roll` representing a critical hit, where the dice are doubled but the flat
+    /// modifiers are only applied once. Each die roll term is repeated with the faces already
+    /// rolled, so a `2d6+5` roll of `[3, 4]` becomes `2d6[3, 4]+2d6[3, 4]+5 (Total: 19)`.
+    ///
+    /// The expression of the new roll (`drex`) reflects the doubled dice, so iterating over it
+    /// produces further critical rolls. Dice that are multiplied or divided are doubled before
+    /// the operator applies, so a `1d6*10` roll of `[4]` becomes `(1d6[4]+1d6[4])*10 (Total: 80)`.
+    pub fn crit_double_dice(&self) -> Roll {
+        let mut values = Vec::new();
+        let expr = self.expr.map_terms(&mut |i| {
+            let v = &self.values[i];
+            values.push(v.clone());
+            match v.0 {
+                DieRollTerm::DieRoll { .. } | DieRollTerm::Fudge { .. } | DieRollTerm::Custom { .. } => {
+                    values.push(v.clone());
+                    Expr::Sum(vec![Expr::Term(values.len() - 2), Expr::Term(values.len() - 1)])
+                }
+                DieRollTerm::Modifier(_) => Expr::Term(values.len() - 1),
+            }
+        });
+        let terms: Vec<DieRollTerm> = values.iter().map(|v| v.0.clone()).collect();
+        let drex = expr.format(&|i, signed| signed_term(signed, &terms[i]));
+
+        Roll {
+            raw: drex.clone(),
+            label: self.label.clone(),
+            drex,
+            total: expr.evaluate(&subtotals(&values)).unwrap_or(self.total),
+            values,
+            #[cfg(feature = "std")]
+            metadata: self.metadata.clone(),
+            expr,
+        }
+    }
+
+    /// Attaches a piece of metadata to this roll, replacing any previous value for `key`.
+    ///
+    /// ```rust
+    /// # extern crate d20;
+    /// # fn main() {
+    ///     let r = d20::roll_dice("1d20+5").unwrap().with_tag("roller", "Mira").with_tag("for", "attack");
+    ///     assert_eq!(r.metadata["roller"], "Mira");
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn with_tag(mut self, key: &str, value: &str) -> Roll {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Rolls the expression in `drex` again, replacing the values and total of this roll with the
+    /// new results. Metadata is kept. If `drex` no longer parses, the roll is left unchanged and
+    /// the error returned.
+    ///
+    /// ```rust
+    /// # extern crate d20;
+    /// # fn main() {
+    ///     let mut r = d20::roll_dice("3d6").unwrap();
+    ///     for _ in 0..3 {
+    ///         r.reroll().unwrap();
+    ///         assert!(r.total >= 3 && r.total <= 18);
+    ///     }
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn reroll(&mut self) -> Result<(), RollError> {
+        *self = self.rolled()?;
+        Ok(())
+    }
+
+    /// Returns a new roll of the expression in `drex`, carrying over the metadata of this roll
+    /// and leaving this roll as it is.
+    #[cfg(feature = "std")]
+    pub fn rolled(&self) -> Result<Roll, RollError> {
+        let mut r = roll_dice(&self.drex)?;
+        r.raw = self.raw.clone();
+        r.label = self.label.clone();
+        r.metadata = self.metadata.clone();
+        Ok(r)
+    }
 }
 
 
@@ -114,168 +730,3010 @@ pub struct Roll {
 /// might result in the following text:
 ///
 /// `3d6[3,4,6]+5 (Total: 18)`
+///
+/// Exploding dice show the chain of rolls each die produced, so a `2d10open` roll where the
+/// first die exploded might result in `2d10open[10→7, 4] (Total: 21)`.
+///
+/// Dice pools mark the dice that succeeded, and a roll made up only of pools counts its total
+/// in successes, as in `6d10>=7[3, 9*, 7*, 2, 10*, 5] (Total: 3 successes)`.
+///
+/// The `+` flag (`format!("{:+}", roll)`) annotates each die roll term with its signed
+/// result, as in `20-2d6[3, 4]=-7 (Total: 13)`.
+///
+/// The alternate flag (`format!("{:#}", roll)`) leaves out the individual dice, showing only the
+/// expression and its total, as in `5d6+2 (Total: 18)`.
+///
+/// A labeled roll shows its label before the total, as in `1d8[5]+3 [fire damage] (Total: 8)`.
 impl fmt::Display for Roll {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {        
-        let mut out = String::new();
-
-        for i in 0..self.values.len() {
-            let ref val = self.values[i];
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let subtotals = self.subtotals();
+        let out = self.expr.format(&|i, signed| {
+            let val = &self.values[i];
             match val.0 {
-                DieRollTerm::Modifier(_) => out = out + format!("{}", &val.0).as_str(),
-                DieRollTerm::DieRoll { .. } => {
-                    out = out + format!("{}{:?}", &val.0, val.1).as_str();
+                DieRollTerm::Modifier(_) => signed_term(signed, &val.0),
+                _ if f.alternate() => signed_term(signed, &val.0),
+                DieRollTerm::DieRoll { .. } | DieRollTerm::Fudge { .. } | DieRollTerm::Custom { .. } => {
+                    let mut out = format!("{}{}", signed_term(signed, &val.0), val.0.format_values(&val.1));
+                    if f.sign_plus() {
+                        out += format!("={}", subtotals[i]).as_str();
+                    }
+                    out
                 }
-            };
+            }
+        });
+        let out = match self.label {
+            Some(ref label) => format!("{} [{}]", out, label),
+            None => out,
+        };
+        match self.success_margin(0) {
+            Some(_) if self.total == 1 => write!(f, "{} (Total: 1 success)", out),
+            Some(_) => write!(f, "{} (Total: {} successes)", out, self.total),
+            None => write!(f, "{} (Total: {})", out, self.total),
         }
-        out = format!("{} (Total: {})", out, self.total);
-        write!(f, "{}", out)
+    }
+}
+
+/// Rolls compare by their `total` alone, so the highest of several rolls can be found with
+/// `max()`. Rolls of different expressions, or with different dice, are equal whenever their
+/// totals are.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let rolls: Vec<d20::Roll> = d20::roll_times("1d20", 4).unwrap().collect::<Result<_, _>>().unwrap();
+///     let best = rolls.iter().max().unwrap();
+///     assert!(rolls.iter().all(|r| r.total <= best.total));
+///     assert_eq!(d20::roll_dice("2d1").unwrap(), d20::roll_dice("1d1+1").unwrap());
+/// # }
+/// ```
+impl PartialEq for Roll {
+    fn eq(&self, other: &Roll) -> bool {
+        self.total == other.total
+    }
+}
+
+impl Eq for Roll {}
+
+impl PartialOrd for Roll {
+    fn partial_cmp(&self, other: &Roll) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Roll {
+    fn cmp(&self, other: &Roll) -> cmp::Ordering {
+        self.total.cmp(&other.total)
     }
 }
 
 /// Converts an evaluated roll expression into an iterator, allowing the expression
-/// to be evaluated (including re-rolling of dice) multiple times. 
+/// to be evaluated (including re-rolling of dice) multiple times. The iterator rolls the terms
+/// of this roll again rather than parsing `drex` each time.
+#[cfg(feature = "std")]
 impl IntoIterator for Roll {
-    type Item = Roll;
+    type Item = Result<Roll, RollError>;
     type IntoIter = RollIterator;
 
     fn into_iter(self) -> Self::IntoIter {
-        RollIterator {
-            roll: self,
-            index: 0,
+        RollExpr {
+            drex: self.drex,
+            raw: self.raw,
+            label: self.label,
+            terms: self.values.into_iter().map(|v| v.0).collect(),
+            expr: self.expr,
         }
+        .into_iter()
     }
 }
 
-/// A `RollIterator` is created when `into_iter()` is called on a `Roll`.
+/// A `RollIterator` is created when `into_iter()` is called on a `Roll` or a `RollExpr`.
+///
+/// The expression is parsed before the iterator is created, so the only rolls that fail are
+/// those that divide by a result that came up zero, as in `1d6/(1d2-1)`, or whose total
+/// overflows. A failed roll is yielded as an error rather than ending the iteration, and the
+/// rolls after it carry on as normal.
+#[cfg(feature = "std")]
 pub struct RollIterator {
-    roll: Roll,
-    index: usize,
+    expr: RollExpr,
 }
 
+#[cfg(feature = "std")]
 impl Iterator for RollIterator {
-    type Item = Roll;
+    type Item = Result<Roll, RollError>;
 
-    fn next(&mut self) -> Option<Roll> {
-        let result = roll_dice(&self.roll.drex);
-        match result {
-            Ok(r) => {
-                self.index += 1;
-                Some(r)
-            }
-            Err(_) => return None,
+    fn next(&mut self) -> Option<Result<Roll, RollError>> {
+        Some(self.expr.roll())
+    }
+}
+
+/// A die roll expression that has been parsed once and can be rolled any number of times.
+/// `roll_dice()` parses its expression on every call, so an expression that is rolled over and
+/// over is cheaper to keep as a `RollExpr`.
+///
+/// ```rust
+/// # extern crate d20;
+/// # use d20::RollExpr;
+/// # fn main() {
+///     let attack = RollExpr::parse("1d20 + 5").unwrap();
+///     for _ in 0..3 {
+///         let r = attack.roll().unwrap();
+///         assert!(r.total >= 6 && r.total <= 25);
+///     }
+///     assert!(RollExpr::parse("roll four chickens").is_err());
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RollExpr {
+    drex: String,
+    raw: String,
+    label: Option<String>,
+    terms: Vec<DieRollTerm>,
+    expr: Expr,
+}
+
+impl RollExpr {
+    /// Parses a die roll expression, failing for the same expressions `roll_dice()` rejects.
+    pub fn parse(s: &str) -> Result<RollExpr, RollError> {
+        let (expression, label) = split_label(s);
+        let (drex, terms, expr) = parse_input(expression)?;
+        Ok(RollExpr { drex, raw: s.to_string(), label, terms, expr })
+    }
+
+    /// Rolls the expression. This only fails when the expression divides by a result that came
+    /// up zero, as in `1d6/(1d2-1)`.
+    #[cfg(feature = "std")]
+    pub fn roll(&self) -> Result<Roll, RollError> {
+        self.roll_with(&mut thread_rng())
+    }
+
+    /// Rolls the expression like `roll()`, drawing every die from `rng` instead of the
+    /// thread-local generator.
+    pub fn roll_with<R: Rng>(&self, rng: &mut R) -> Result<Roll, RollError> {
+        self.roll_within(usize::MAX, rng)
+    }
+
+    /// Rolls the expression, failing once more than `work_budget` dice have been rolled.
+    fn roll_within<R: Rng>(&self, work_budget: usize, rng: &mut R) -> Result<Roll, RollError> {
+        let mut remaining = work_budget;
+        let mut v = Vec::with_capacity(self.terms.len());
+        for t in &self.terms {
+            let values = t.roll_within(&mut remaining, rng)?;
+            v.push((t.clone(), values));
+        }
+
+        Ok(Roll {
+            drex: self.drex.clone(),
+            raw: self.raw.clone(),
+            label: self.label.clone(),
+            total: self.expr.evaluate(&subtotals(&v))?,
+            values: v,
+            #[cfg(feature = "std")]
+            metadata: HashMap::new(),
+            expr: self.expr.clone(),
+        })
+    }
+}
+
+/// Parses a die roll expression, as `RollExpr::parse()` does.
+impl FromStr for RollExpr {
+    type Err = RollError;
+
+    fn from_str(s: &str) -> Result<RollExpr, RollError> {
+        RollExpr::parse(s)
+    }
+}
+
+/// Converts a parsed roll expression into an iterator of successive rolls of it.
+#[cfg(feature = "std")]
+impl IntoIterator for RollExpr {
+    type Item = Result<Roll, RollError>;
+    type IntoIter = RollIterator;
+
+    fn into_iter(self) -> Self::IntoIter {
+        RollIterator { expr: self }
+    }
+}
+
+/// Builds a `RollExpr` from its terms in code rather than from a string, adding up the terms
+/// in the order they are given.
+///
+/// ```rust
+/// # extern crate d20;
+/// # use d20::RollBuilder;
+/// # fn main() {
+///     let expr = RollBuilder::new().die(3, 6).modifier(5).die(-1, 4).build().unwrap();
+///     let r = expr.roll().unwrap();
+///     assert_eq!(r.drex, "3d6+5-1d4");
+///     assert!(r.total >= 4 && r.total <= 22);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RollBuilder {
+    terms: Vec<DieRollTerm>,
+}
+
+impl RollBuilder {
+    /// Starts an expression with no terms.
+    pub fn new() -> RollBuilder {
+        RollBuilder::default()
+    }
+
+    /// Adds `multiplier` dice with `sides` sides, subtracting them if `multiplier` is negative.
+    pub fn die(self, multiplier: i16, sides: u16) -> RollBuilder {
+        self.term(DieRollTerm::DieRoll {
+            multiplier,
+            sides,
+            open_ended: None,
+            exploding: false,
+            compounding: false,
+            penetrating: false,
+            reroll: None,
+            min: None,
+            max: None,
+            keep: None,
+            success: None,
+        })
+    }
+
+    /// Adds a constant modifier, subtracting it if `n` is negative.
+    pub fn modifier(self, n: i32) -> RollBuilder {
+        self.term(DieRollTerm::Modifier(n))
+    }
+
+    /// Adds any die roll term, such as exploding dice or a dice pool.
+    pub fn term(mut self, term: DieRollTerm) -> RollBuilder {
+        self.terms.push(term);
+        self
+    }
+
+    /// Finishes the expression, failing if it has no terms or if any of its terms could not be
+    /// rolled, such as a die with no sides.
+    pub fn build(self) -> Result<RollExpr, RollError> {
+        if self.terms.is_empty() {
+            return Err(RollError::EmptyExpression);
+        }
+        for t in &self.terms {
+            t.check()?;
         }
+        let expr = Expr::Sum((0..self.terms.len()).map(Expr::Term).collect());
+        let drex = expr.format(&|i, signed| signed_term(signed, &self.terms[i]));
+        Ok(RollExpr { raw: drex.clone(), drex, label: None, terms: self.terms, expr })
     }
 }
 
 /// Represents an individual term within a die roll expression. Terms can either be numeric
 /// modifiers like `+5` or `-2` or they can be terms indicating die rolls.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DieRollTerm {
     /// Indicates a die roll term to roll `multiplier` dice with `sides` sides.
     DieRoll {
         /// Number of times to roll the given die
-        multiplier: i8,
+        multiplier: i16,
         /// Number of sides on the given die
-        sides: u8,
+        sides: u16,
+        /// When set, the dice are _open-ended_ in both directions (e.g. `1d100open`): a roll
+        /// within this many faces of the top is rolled again and added, while a roll within
+        /// this many faces of the bottom is rolled again and subtracted.
+        open_ended: Option<u16>,
+        /// When set, the dice are _exploding_ (e.g. `3d6!`): a die showing its highest face is
+        /// rolled again and the new roll added, repeating for as long as the highest face comes up.
+        exploding: bool,
+        /// When set along with `exploding`, the dice are _compounding_ (e.g. `1d6!!`): the rolls
+        /// of each exploding die are added up and recorded as a single face.
+        compounding: bool,
+        /// When set along with `exploding`, the dice are _penetrating_ (e.g. `1d6!p`): every roll
+        /// after the first of an exploding die counts one less than the face it shows.
+        penetrating: bool,
+        /// When set, any die showing a face that satisfies this comparison is rolled once more
+        /// and the new face kept, even if it satisfies the comparison again (e.g. `2d6ro<3`, or
+        /// `4d6r1` to reroll a face of 1 or less).
+        reroll: Option<Comparison>,
+        /// When set, a die showing a lower face counts as showing this face instead (e.g.
+        /// `2d6min3`), although the face actually rolled is still recorded.
+        min: Option<u16>,
+        /// When set, a die showing a higher face counts as showing this face instead (e.g.
+        /// `3d8max6`), although the face actually rolled is still recorded.
+        max: Option<u16>,
+        /// When set, only some of the dice count towards the result (e.g. `4d6kh3`), although
+        /// every die rolled is still recorded.
+        keep: Option<Keep>,
+        /// When set, the term is a _dice pool_ (e.g. `6d10>=7`): rather than summing the faces,
+        /// it counts how many dice satisfy this comparison.
+        success: Option<Comparison>,
+    },
+    /// Indicates a roll of `multiplier` _Fudge dice_ (e.g. `4dF`), as used by Fate: each die
+    /// shows -1, 0 or +1 with equal chance.
+    Fudge {
+        /// Number of times to roll a Fudge die
+        multiplier: i16,
+    },
+    /// Indicates a roll of `multiplier` dice with custom faces (e.g. `3d[2,3,3,4,4,5]`): each die
+    /// shows one of `faces` with equal chance, so a face listed twice comes up twice as often.
+    Custom {
+        /// Number of times to roll the die
+        multiplier: i16,
+        /// The faces of the die, which may be negative
+        faces: Vec<i32>,
     },
     /// Numeric modifier used in simple left-to-right numeric evaluation of a die roll expression.
-    Modifier(i8),
+    Modifier(i32),
 }
 
 
 impl DieRollTerm {
+    #[cfg(all(test, feature = "std"))]
     fn parse(drt: &str) -> DieRollTerm {
-        if drt.to_lowercase().contains('d') {
-            let v: Vec<&str> = drt.split("d").collect();
-            DieRollTerm::DieRoll {
-                multiplier: v[0].parse::<i8>().unwrap(),
-                sides: v[1].parse::<u8>().unwrap(),
+        DieRollTerm::try_parse(drt).unwrap()
+    }
+
+    /// Parses a single die roll term matched by `TERM_PATTERN`, failing only if one of its
+    /// numbers is too large to represent.
+    fn try_parse(drt: &str) -> Result<DieRollTerm, RollError> {
+        if drt.contains(['d', 'D']) {
+            let v: Vec<&str> = drt.splitn(2, ['d', 'D']).collect();
+            if v[1].eq_ignore_ascii_case("f") {
+                return Ok(DieRollTerm::Fudge { multiplier: dice_count(v[0])? });
+            }
+            if v[1].starts_with('[') {
+                let faces = v[1][1..v[1].len() - 1]
+                    .split(',')
+                    .map(number::<i32>)
+                    .collect::<Result<Vec<i32>, RollError>>()?;
+                return Ok(DieRollTerm::Custom { multiplier: dice_count(v[0])?, faces });
+            }
+            // `d%` and `d00` are both percentile dice, i.e. `d100`
+            let (sides, mut suffix) = match v[1].strip_prefix('%') {
+                Some(rest) => ("100", rest),
+                None => split_number(v[1]),
+            };
+            let sides = match sides {
+                "00" => 100,
+                n => number::<u16>(n)?,
+            };
+
+            let mut open_ended = None;
+            if suffix.to_lowercase().starts_with("open") {
+                let (n, rest) = split_number(&suffix["open".len()..]);
+                open_ended = match n {
+                    "" => Some(default_open_threshold(sides)),
+                    n => Some(number::<u16>(n)?),
+                };
+                suffix = rest;
+            }
+            let exploding = suffix.starts_with('!');
+            if exploding {
+                suffix = &suffix[1..];
+            }
+            let compounding = exploding && suffix.starts_with('!');
+            let penetrating = exploding && suffix.to_lowercase().starts_with('p');
+            if compounding || penetrating {
+                suffix = &suffix[1..];
+            }
+            let mut reroll = None;
+            if suffix.to_lowercase().starts_with("ro") {
+                let (c, rest) = split_comparison(&suffix["ro".len()..]);
+                reroll = Some(Comparison::parse(c)?);
+                suffix = rest;
+            } else if suffix.to_lowercase().starts_with('r') {
+                // `r2` is shorthand for rerolling a 2 or less once, i.e. `ro<=2`
+                let (n, rest) = split_number(&suffix["r".len()..]);
+                reroll = Some(Comparison::AtMost(number::<i32>(n)?));
+                suffix = rest;
             }
+            let mut min = None;
+            if suffix.to_lowercase().starts_with("min") {
+                let (n, rest) = split_number(&suffix["min".len()..]);
+                min = Some(number::<u16>(n)?);
+                suffix = rest;
+            }
+            let mut max = None;
+            if suffix.to_lowercase().starts_with("max") {
+                let (n, rest) = split_number(&suffix["max".len()..]);
+                max = Some(number::<u16>(n)?);
+                suffix = rest;
+            }
+            let mut keep = None;
+            let lower = suffix.to_lowercase();
+            if lower.starts_with("kh") || lower.starts_with("kl") {
+                let (n, rest) = split_number(&suffix["kh".len()..]);
+                let n = match n {
+                    "" => 1,
+                    n => number::<u16>(n)?,
+                };
+                keep = if lower.starts_with("kh") { Some(Keep::Highest(n)) } else { Some(Keep::Lowest(n)) };
+                suffix = rest;
+            }
+            let success = if suffix.is_empty() {
+                None
+            } else {
+                Some(Comparison::parse(suffix)?)
+            };
+
+            Ok(DieRollTerm::DieRoll {
+                multiplier: dice_count(v[0])?,
+                sides,
+                open_ended,
+                exploding,
+                compounding,
+                penetrating,
+                reroll,
+                min,
+                max,
+                keep,
+                success,
+            })
         } else {
-            DieRollTerm::Modifier(drt.parse::<i8>().unwrap())
+            Ok(DieRollTerm::Modifier(number::<i32>(drt)?))
         }
     }
 
 
-    fn calculate(v: (DieRollTerm, Vec<i8>)) -> i32 {
-        match v.0 {
-            DieRollTerm::Modifier(n) => n as i32,
-            DieRollTerm::DieRoll { multiplier: m, .. } => {
-                let mut sum: i32 = v.1.iter().fold(0i32, |sum, &val| sum + val as i32);
+    /// Returns the signed result of an evaluated term, one of the `values` of a `Roll`, as it
+    /// counts towards the total. The roll is only borrowed, so it can still be used afterwards.
+    ///
+    /// ```rust
+    /// # extern crate d20;
+    /// # use d20::DieRollTerm;
+    /// # fn main() {
+    ///     let r = d20::roll_dice("3d1 - 2d1").unwrap();
+    ///     assert_eq!(DieRollTerm::calculate(&r.values[1]), -2);
+    ///     assert_eq!(r.values.iter().map(DieRollTerm::calculate).sum::<i64>(), r.total);
+    /// # }
+    /// ```
+    pub fn calculate(v: &(DieRollTerm, Vec<i32>)) -> i64 {
+        v.0.sum(&v.1)
+    }
+
+    fn sum(&self, values: &[i32]) -> i64 {
+        match *self {
+            DieRollTerm::Modifier(n) => i64::from(n),
+            DieRollTerm::DieRoll { multiplier: m, success, .. } => {
+                // the dice are scored by magnitude first; a negative multiplier only flips the
+                // sign of the finished result, so every modifier sees the dice as rolled
+                let faces = self.faces(values);
+                let magnitude = match success {
+                    Some(c) => faces.iter().filter(|&&val| c.matches(val)).count() as i64,
+                    None => faces.iter().map(|&val| i64::from(val)).sum(),
+                };
+                if m < 0 {
+                    -magnitude
+                } else {
+                    magnitude
+                }
+            }
+            DieRollTerm::Fudge { multiplier: m } | DieRollTerm::Custom { multiplier: m, .. } => {
+                let magnitude: i64 = values.iter().map(|&val| i64::from(val)).sum();
                 if m < 0 {
-                    sum = sum * -1;
+                    -magnitude
+                } else {
+                    magnitude
                 }
-                sum
             }
         }
     }
 
-    fn evaluate(self) -> (DieRollTerm, Vec<i8>) {
-        match self {
-            DieRollTerm::Modifier(n) => (self, vec![n]),
-            DieRollTerm::DieRoll { multiplier: m, sides: s } => {
-                (self, (0..m.abs()).map(|_| thread_rng().gen_range(1, s as i8 + 1)).collect())
+    /// Rejects terms that are well-formed but structurally impossible to evaluate, naming the
+    /// conflict so callers can fail fast instead of rolling a degenerate result.
+    fn validate(&self) -> Result<(), &'static str> {
+        match *self {
+            DieRollTerm::Modifier(_) | DieRollTerm::Fudge { .. } => Ok(()),
+            DieRollTerm::Custom { ref faces, .. } if faces.is_empty() => {
+                Err("a custom die must have at least one face")
+            }
+            DieRollTerm::Custom { .. } => Ok(()),
+            DieRollTerm::DieRoll { sides: 0, .. } => {
+                Err("a die must have at least one side")
+            }
+            DieRollTerm::DieRoll { sides: s, open_ended: Some(n), .. }
+                if u32::from(n) * 2 >= u32::from(s) => {
+                Err("open-ended thresholds overlap on every face")
+            }
+            DieRollTerm::DieRoll { open_ended: Some(_), success: Some(_), .. } => {
+                Err("open-ended dice cannot be counted as a dice pool")
+            }
+            DieRollTerm::DieRoll { open_ended: Some(_), reroll: Some(_), .. } => {
+                Err("open-ended dice cannot be rerolled")
+            }
+            DieRollTerm::DieRoll { sides: 1, exploding: true, .. } => {
+                Err("a one-sided die would explode forever")
+            }
+            DieRollTerm::DieRoll { open_ended: Some(_), exploding: true, .. } => {
+                Err("open-ended dice cannot also explode")
+            }
+            DieRollTerm::DieRoll { exploding: true, reroll: Some(_), .. } => {
+                Err("exploding dice cannot be rerolled")
             }
+            DieRollTerm::DieRoll { sides: s, min: Some(n), .. } if n == 0 || n > s => {
+                Err("a minimum must be one of the faces of the die")
+            }
+            DieRollTerm::DieRoll { sides: s, max: Some(n), .. } if n == 0 || n > s => {
+                Err("a maximum must be one of the faces of the die")
+            }
+            DieRollTerm::DieRoll { min: Some(low), max: Some(high), .. } if low > high => {
+                Err("the minimum cannot be greater than the maximum")
+            }
+            DieRollTerm::DieRoll { open_ended: Some(_), min: Some(_), .. } => {
+                Err("open-ended dice cannot have a minimum")
+            }
+            DieRollTerm::DieRoll { open_ended: Some(_), max: Some(_), .. } => {
+                Err("open-ended dice cannot have a maximum")
+            }
+            DieRollTerm::DieRoll { multiplier: m, keep: Some(k), .. } if k.count() > m.unsigned_abs() => {
+                Err("cannot keep more dice than are rolled")
+            }
+            DieRollTerm::DieRoll { .. } => Ok(()),
         }
     }
-}
 
-/// Formats an individual die roll term in a human-friendly fashion. For `Modifier` terms,
-/// this will force the printing of a + or - sign before the modifier value. For `DieRoll`
-/// terms, this displays the term in the form `5d10`. 
-impl fmt::Display for DieRollTerm {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    /// Validates the term, reporting any conflict as a `RollError` that names the term.
+    fn check(&self) -> Result<(), RollError> {
+        self.validate().map_err(|reason| RollError::InvalidTerm { term: self.to_string(), reason })
+    }
+
+    fn variance(&self) -> Result<f64, RollError> {
         match *self {
-            DieRollTerm::Modifier(n) => write!(f, "{:+}", n),
-            DieRollTerm::DieRoll { multiplier: m, sides: s } => write!(f, "{}d{}", m, s),
+            DieRollTerm::Modifier(_) => Ok(0.0),
+            // each Fudge die is -1, 0 or +1 with a mean of 0, so its variance is 2/3
+            DieRollTerm::Fudge { multiplier: m } => Ok(f64::from(m.unsigned_abs()) * 2.0 / 3.0),
+            DieRollTerm::Custom { multiplier: m, ref faces } => {
+                let mean = custom_mean(faces);
+                let spread = faces.iter().map(|&f| powi(f64::from(f) - mean, 2)).sum::<f64>();
+                Ok(f64::from(m.unsigned_abs()) * spread / faces.len() as f64)
+            }
+            DieRollTerm::DieRoll { .. } if self.explodes() => {
+                Err(RollError::Unsupported("exploding dice have no exact variance"))
+            }
+            DieRollTerm::DieRoll { keep: Some(_), .. } => {
+                // kept dice depend on each other, so work from the term's whole distribution
+                let (offset, dist) = self.keep_distribution()?;
+                let moment = |k: i32| -> f64 {
+                    dist.iter().enumerate().map(|(i, p)| powi((offset + i as i64) as f64, k) * p).sum()
+                };
+                let mean = moment(1);
+                Ok(moment(2) - mean * mean)
+            }
+            DieRollTerm::DieRoll { multiplier: m, success: Some(c), .. } => {
+                // each die of a pool is an independent success/failure trial
+                let p = c.probability(&self.face_distribution());
+                Ok(f64::from(m.unsigned_abs()) * p * (1.0 - p))
+            }
+            DieRollTerm::DieRoll { multiplier: m, .. } => {
+                let dist = self.face_distribution();
+                let moment = |k: i32| -> f64 {
+                    dist.iter().enumerate().map(|(i, p)| powi(f64::from(i as i32 + 1), k) * p).sum()
+                };
+                let mean = moment(1);
+                Ok(f64::from(m.unsigned_abs()) * (moment(2) - mean * mean))
+            }
         }
     }
-}
-
-/// Evaluates the expression string input as a die roll expression (e.g. 3d6 + 4). The
-/// results are returned in a `Result` object that contains either a valid `Roll` or some
-/// text indicating why the function was unable to roll the dice / evaluate the expression.
-pub fn roll_dice<'a>(s: &'a str) -> Result<Roll, &'a str> {
-    let s: String = s.split_whitespace().collect();
-    let terms: Vec<DieRollTerm> = parse_die_roll_terms(&s);
-
-    if terms.len() == 0 {
-        Err("Invalid die roll expression: no die roll terms found.")
-    } else {
 
-        let v: Vec<_> = terms.into_iter().map(|t| t.evaluate()).collect();
-        let t = v.clone();
+    /// Returns the expected result of this term. Exploding dice are supported as long as they are
+    /// simply added up, with the penalty of penetrating dice taken before their minimum and
+    /// maximum and the whole chain of a compounding die clamped at once, while open-ended dice
+    /// are not.
+    fn mean(&self) -> Result<f64, RollError> {
+        let (m, single) = match *self {
+            DieRollTerm::Modifier(n) => return Ok(f64::from(n)),
+            DieRollTerm::Fudge { .. } => return Ok(0.0),
+            DieRollTerm::Custom { multiplier: m, ref faces } => (m, custom_mean(faces)),
+            DieRollTerm::DieRoll { multiplier: m, sides: s, exploding: true, keep: None, success: None, .. } => {
+                // every further roll of the chain happens only if all the rolls before it showed
+                // the highest face
+                let face: f64 = self.face_distribution()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| f64::from(i as i32 + 1) * p)
+                    .sum();
+                let rolls: f64 = (0..=MAX_EXPLOSIONS as i32).map(|k| powi(f64::from(s), -k)).sum();
+                match *self {
+                    // every roll after the first counts one less, before the minimum and maximum
+                    // of the term apply
+                    DieRollTerm::DieRoll { penetrating: true, .. } => {
+                        let s = i32::from(s);
+                        let penalized = (1..=s).map(|f| f64::from(self.clamp(f - 1))).sum::<f64>() / f64::from(s);
+                        (m, face + (rolls - 1.0) * penalized)
+                    }
+                    // a compounding die is one value summed from its chain, and the minimum and
+                    // maximum apply to that sum: the chain stops at the first face below the
+                    // highest, or once every explosion has been rolled
+                    DieRollTerm::DieRoll { compounding: true, min, max, .. } if min.is_some() || max.is_some() => {
+                        let s = i32::from(s);
+                        let mut total = 0.0;
+                        for k in 0..=MAX_EXPLOSIONS as i32 {
+                            let p = powi(f64::from(s), -(k + 1));
+                            if p == 0.0 {
+                                break;
+                            }
+                            total += (1..s).map(|f| f64::from(self.clamp(s * k + f))).sum::<f64>() * p;
+                        }
+                        let all_highest = MAX_EXPLOSIONS as i32 + 1;
+                        total += powi(f64::from(s), -all_highest) * f64::from(self.clamp(s * all_highest));
+                        (m, total)
+                    }
+                    _ => (m, face * rolls),
+                }
+            }
+            DieRollTerm::DieRoll { .. } if self.explodes() => {
+                return Err(RollError::Unsupported("exploding dice have no exact mean"));
+            }
+            DieRollTerm::DieRoll { keep: Some(_), .. } => {
+                let (offset, dist) = self.keep_distribution()?;
+                return Ok(dist.iter().enumerate().map(|(i, p)| (offset + i as i64) as f64 * p).sum());
+            }
+            DieRollTerm::DieRoll { multiplier: m, success: Some(c), .. } => {
+                (m, c.probability(&self.face_distribution()))
+            }
+            DieRollTerm::DieRoll { multiplier: m, sides: s, reroll: None, min: None, max: None, .. } => {
+                (m, (f64::from(s) + 1.0) / 2.0)
+            }
+            DieRollTerm::DieRoll { multiplier: m, .. } => {
+                let dist = self.face_distribution();
+                (m, dist.iter().enumerate().map(|(i, p)| f64::from(i as i32 + 1) * p).sum())
+            }
+        };
+        Ok(f64::from(m) * single)
+    }
+
+    /// Returns the lowest and highest possible results of this term. Exploding and open-ended
+    /// dice have no useful bounds.
+    fn bounds(&self) -> Result<(i64, i64), RollError> {
+        let (m, low, high) = match *self {
+            DieRollTerm::Modifier(n) => return Ok((i64::from(n), i64::from(n))),
+            DieRollTerm::Fudge { multiplier: m } => {
+                let dice = i64::from(m.unsigned_abs());
+                (m, -dice, dice)
+            }
+            DieRollTerm::Custom { multiplier: m, ref faces } => {
+                let dice = i64::from(m.unsigned_abs());
+                let low = faces.iter().cloned().min().map_or(0, i64::from);
+                let high = faces.iter().cloned().max().map_or(0, i64::from);
+                (m, dice * low, dice * high)
+            }
+            DieRollTerm::DieRoll { .. } if self.explodes() => {
+                return Err(RollError::Unsupported("exploding dice have no upper bound"));
+            }
+            DieRollTerm::DieRoll { multiplier: m, sides, keep, success, .. } => {
+                let dice = i64::from(keep.map_or(m.unsigned_abs(), |k| k.count()));
+                match success {
+                    Some(c) => {
+                        // a die is certain to succeed only if every face it can settle on does,
+                        // and can succeed if any of them does
+                        let faces: Vec<bool> = self.face_distribution()
+                            .iter()
+                            .enumerate()
+                            .filter(|&(_, &p)| p > 0.0)
+                            .map(|(i, _)| c.matches(i as i32 + 1))
+                            .collect();
+                        let all = faces.iter().all(|&f| f);
+                        let any = faces.iter().any(|&f| f);
+                        (m, dice * i64::from(all), dice * i64::from(any))
+                    }
+                    None => {
+                        let (low, high) = (self.clamp(1), self.clamp(i32::from(sides)));
+                        (m, dice * i64::from(low), dice * i64::from(high))
+                    }
+                }
+            }
+        };
+        Ok(if m < 0 { (-high, -low) } else { (low, high) })
+    }
+
+    /// Returns the probability of a single die of this term settling on each face, indexed from
+    /// a face of 1. Dice that reroll some faces are less likely to settle on those faces, and
+    /// faces outside the minimum and maximum of the term never count.
+    ///
+    /// Open-ended dice have no such distribution, as their results are unbounded, and Fudge and
+    /// custom dice have no face of 1 to index from.
+    fn face_distribution(&self) -> Vec<f64> {
+        match *self {
+            DieRollTerm::DieRoll { sides: s, reroll, .. } => {
+                let uniform = 1.0 / f64::from(s);
+                let rerolled = reroll.map_or(0.0, |c| c.probability(&vec![uniform; s as usize]));
+                let mut dist = vec![0.0; s as usize];
+                for f in 1..=i32::from(s) {
+                    dist[self.clamp(f) as usize - 1] += match reroll {
+                        Some(c) if c.matches(f) => rerolled * uniform,
+                        _ => uniform + rerolled * uniform,
+                    };
+                }
+                dist
+            }
+            DieRollTerm::Fudge { .. } | DieRollTerm::Custom { .. } | DieRollTerm::Modifier(_) => Vec::new(),
+        }
+    }
+
+    /// Returns the distribution of the result of a term that keeps only some of its dice, as
+    /// the lowest possible result and the probability of each result from there on. Kept dice
+    /// depend on each other, so every combination of faces is enumerated.
+    fn keep_distribution(&self) -> Result<(i64, Vec<f64>), RollError> {
+        let (m, keep, success) = match *self {
+            DieRollTerm::DieRoll { multiplier: m, keep: Some(k), success, .. } => (m, k, success),
+            _ => return Ok((self.sum(&[]), vec![1.0])),
+        };
+        let dist = self.face_distribution();
+        let dice = m.unsigned_abs() as usize;
+        let outcomes = dist.len()
+            .checked_pow(dice as u32)
+            .filter(|&o| o.saturating_mul(dice) <= MAX_DISTRIBUTION_WORK)
+            .ok_or(RollError::TooLarge)?;
+
+        let mut results = BTreeMap::new();
+        let mut faces = vec![1i32; dice];
+        for _ in 0..outcomes {
+            let p: f64 = faces.iter().map(|&f| dist[f as usize - 1]).product();
+            let kept = keep.select(&faces);
+            let score = faces.iter().zip(kept).filter(|&(_, k)| k).fold(0i32, |sum, (&f, _)| {
+                match success {
+                    Some(c) => sum + c.matches(f) as i32,
+                    None => sum + f,
+                }
+            });
+            *results.entry(if m < 0 { -score } else { score }).or_insert(0.0) += p;
+
+            // advance to the next combination of faces, like an odometer
+            for f in faces.iter_mut() {
+                if (*f as usize) < dist.len() {
+                    *f += 1;
+                    break;
+                }
+                *f = 1;
+            }
+        }
+
+        let offset = *results.keys().next().unwrap_or(&0);
+        let mut p = Vec::new();
+        for (score, prob) in results {
+            p.resize((score - offset) as usize, 0.0);
+            p.push(prob);
+        }
+        Ok((i64::from(offset), p))
+    }
+
+    /// Returns whether the dice of this term can explode, being either exploding or open-ended.
+    fn explodes(&self) -> bool {
+        match *self {
+            DieRollTerm::DieRoll { open_ended, exploding, .. } => exploding || open_ended.is_some(),
+            DieRollTerm::Fudge { .. } | DieRollTerm::Custom { .. } | DieRollTerm::Modifier(_) => false,
+        }
+    }
+
+    /// Splits the faces rolled for this term into the chains rolled by each individual die.
+    /// Only exploding and rerolled dice produce chains longer than a single face, as compounding
+    /// dice record each chain as a single face.
+    fn chains<'a>(&self, values: &'a [i32]) -> Vec<&'a [i32]> {
+        match *self {
+            DieRollTerm::DieRoll { reroll: Some(c), .. } => {
+                let mut chains = Vec::new();
+                let mut i = 0;
+                while i < values.len() {
+                    let end = if c.matches(values[i]) { cmp::min(i + 2, values.len()) } else { i + 1 };
+                    chains.push(&values[i..end]);
+                    i = end;
+                }
+                chains
+            }
+            DieRollTerm::DieRoll { compounding: true, .. } => values.chunks(1).collect(),
+            DieRollTerm::DieRoll { sides: s, exploding: true, .. } => {
+                let mut chains = Vec::new();
+                let mut i = 0;
+                while i < values.len() {
+                    let mut end = i + 1;
+                    while values[end - 1] == s as i32 && end < values.len() && end - i <= MAX_EXPLOSIONS {
+                        end += 1;
+                    }
+                    chains.push(&values[i..end]);
+                    i = end;
+                }
+                chains
+            }
+            DieRollTerm::DieRoll { sides: s, open_ended: Some(n), .. } => {
+                let high = s as i32 - n as i32;
+                let mut chains = Vec::new();
+                let mut i = 0;
+                while i < values.len() {
+                    let mut end = i + 1;
+                    if values[i] > high || values[i] <= n as i32 {
+                        while end < values.len() && end - i <= MAX_EXPLOSIONS {
+                            end += 1;
+                            if values[end - 1].abs() <= high {
+                                break;
+                            }
+                        }
+                    }
+                    chains.push(&values[i..end]);
+                    i = end;
+                }
+                chains
+            }
+            _ => values.chunks(1).collect(),
+        }
+    }
+
+    /// Returns the face that counts for a die of this term that rolled `face`, raised to the
+    /// minimum of the term and lowered to its maximum if it has them.
+    fn clamp(&self, face: i32) -> i32 {
+        match *self {
+            DieRollTerm::DieRoll { min, max, .. } => {
+                let face = min.map_or(face, |n| cmp::max(face, i32::from(n)));
+                max.map_or(face, |n| cmp::min(face, i32::from(n)))
+            }
+            _ => face,
+        }
+    }
+
+    /// Returns the faces rolled for this term that count towards its result, leaving out any
+    /// faces that were discarded by a reroll and the dice that were not kept. Faces outside the
+    /// minimum and maximum of the term count as the nearest of them.
+    fn faces(&self, values: &[i32]) -> Vec<i32> {
+        let chains = self.chains(values);
+        let kept = self.kept(&chains);
+        chains
+            .into_iter()
+            .zip(kept)
+            .filter(|&(_, k)| k)
+            .flat_map(|(c, _)| self.counted(c))
+            .collect()
+    }
+
+    /// Returns the faces of a single die's chain of rolls that count towards the result of this
+    /// term: the last face of a rerolled die, every face of any other die less the penalty of
+    /// penetrating dice, each raised to the minimum and lowered to the maximum of the term.
+    fn counted(&self, chain: &[i32]) -> Vec<i32> {
+        match *self {
+            DieRollTerm::DieRoll { reroll: Some(_), .. } => vec![self.clamp(chain[chain.len() - 1])],
+            DieRollTerm::DieRoll { penetrating: true, .. } => chain
+                .iter()
+                .enumerate()
+                .map(|(i, &f)| self.clamp(if i == 0 { f } else { f - 1 }))
+                .collect(),
+            _ => chain.iter().map(|&f| self.clamp(f)).collect(),
+        }
+    }
+
+    /// Returns whether each die of this term, given as the chains of rolls from `chains()`,
+    /// counts towards its result.
+    ///
+    /// Dice are kept by what they count as, so the minimum and maximum of the term and the
+    /// penalty of penetrating dice apply before the highest or lowest dice are chosen. None of
+    /// these ever turns a lower die into a higher one, so the order never changes the result,
+    /// only which of the dice they leave tied is dropped: the first rolled is kept.
+    fn kept(&self, chains: &[&[i32]]) -> Vec<bool> {
+        match *self {
+            DieRollTerm::DieRoll { keep: Some(k), .. } => {
+                let results: Vec<i32> = chains.iter().map(|c| self.counted(c).iter().sum()).collect();
+                k.select(&results)
+            }
+            _ => vec![true; chains.len()],
+        }
+    }
+
+    /// Formats the faces rolled for this term, showing each die's chain of rolls: exploded dice
+    /// as `6→5→2` and rerolled dice with their discarded face in parentheses, as `(1), 4`. Dice
+    /// that were not kept are wrapped in parentheses whole, as `(7)` or `(1, 4)`.
+    ///
+    /// The dice of a pool that count as successes are marked with a `*`, as in `[3, 9*, 7*, 2]`,
+    /// and Fudge dice show their faces as `+`, `0` and `-`, as in `[+, 0, -, -]`. A die raised
+    /// to the minimum of its term shows both faces, as `1↑3`, as does a die lowered to the
+    /// maximum, as `8↓6`, or a penetrating die counting one less, as `6↓5`.
+    fn format_values(&self, values: &[i32]) -> String {
+        if let DieRollTerm::Fudge { .. } = *self {
+            let faces: Vec<&str> = values
+                .iter()
+                .map(|&f| match f.signum() {
+                    1 => "+",
+                    -1 => "-",
+                    _ => "0",
+                })
+                .collect();
+            return format!("[{}]", faces.join(", "));
+        }
+        let success = match *self {
+            DieRollTerm::DieRoll { success, .. } => success,
+            _ => None,
+        };
+        // shows a face `f` that counts as `clamped`
+        let face = |f: i32, clamped: i32, counted: bool| {
+            let shown = match clamped.cmp(&f) {
+                cmp::Ordering::Equal => f.to_string(),
+                cmp::Ordering::Greater => format!("{}↑{}", f, clamped),
+                cmp::Ordering::Less => format!("{}↓{}", f, clamped),
+            };
+            match success {
+                Some(c) if counted && c.matches(clamped) => format!("{}*", shown),
+                _ => shown,
+            }
+        };
+        let chains = self.chains(values);
+        let kept = self.kept(&chains);
+        let chains: Vec<String> = chains
+            .iter()
+            .zip(kept)
+            .map(|(c, k)| match *self {
+                DieRollTerm::DieRoll { reroll: Some(_), .. } if !k => {
+                    format!("({})", c.iter().map(|&f| face(f, self.clamp(f), false)).collect::<Vec<_>>().join(", "))
+                }
+                DieRollTerm::DieRoll { reroll: Some(_), .. } if c.len() > 1 => {
+                    format!("({}), {}", c[0], face(c[1], self.clamp(c[1]), true))
+                }
+                _ => {
+                    let chain = c.iter()
+                        .zip(self.counted(c))
+                        .map(|(&f, clamped)| face(f, clamped, k))
+                        .collect::<Vec<_>>()
+                        .join("→");
+                    if k { chain } else { format!("({})", chain) }
+                }
+            })
+            .collect();
+        format!("[{}]", chains.join(", "))
+    }
+
+    #[cfg(all(test, feature = "std"))]
+    fn evaluate(self) -> (DieRollTerm, Vec<i32>) {
+        let mut values = Vec::new();
+        self.roll_into(&mut values, &mut thread_rng());
+        (self, values)
+    }
+
+    /// Rolls the term, charging every die rolled (explosions and rerolls included) against the
+    /// `remaining` work budget and giving up as soon as the budget runs out.
+    fn roll_within<R: Rng>(&self, remaining: &mut usize, rng: &mut R) -> Result<Vec<i32>, RollError> {
+        let mut values = Vec::new();
+        match *self {
+            DieRollTerm::Modifier(n) => values.push(n),
+            DieRollTerm::DieRoll { multiplier: m, .. }
+            | DieRollTerm::Fudge { multiplier: m }
+            | DieRollTerm::Custom { multiplier: m, .. } => {
+                for _ in 0..m.unsigned_abs() {
+                    let draws = self.roll_die_into(&mut values, rng);
+                    *remaining = remaining.checked_sub(draws).ok_or(RollError::BudgetExceeded)?;
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// Rolls the term, appending the resulting die faces (or the modifier value) to `values`.
+    #[cfg(feature = "std")]
+    fn roll_into<R: Rng>(&self, values: &mut Vec<i32>, rng: &mut R) {
+        match *self {
+            DieRollTerm::Modifier(n) => values.push(n),
+            DieRollTerm::DieRoll { multiplier: m, .. }
+            | DieRollTerm::Fudge { multiplier: m }
+            | DieRollTerm::Custom { multiplier: m, .. } => {
+                for _ in 0..m.unsigned_abs() {
+                    self.roll_die_into(values, rng);
+                }
+            }
+        }
+    }
+
+    /// Rolls a single die of the term, appending every face it produced (one, or a whole chain
+    /// for exploding and rerolled dice) to `values`. A compounding die appends the sum of its
+    /// chain as one face, while a penetrating die appends its faces as rolled. Returns how many
+    /// times the die was drawn from `rng`, which for a compounding die is more than the one face
+    /// it appends.
+    fn roll_die_into<R: Rng>(&self, values: &mut Vec<i32>, rng: &mut R) -> usize {
+        let before = values.len();
+        match *self {
+            DieRollTerm::Modifier(_) => {}
+            DieRollTerm::Fudge { .. } => values.push(rng.gen_range(-1, 2)),
+            DieRollTerm::Custom { ref faces, .. } => values.push(faces[rng.gen_range(0, faces.len())]),
+            DieRollTerm::DieRoll { sides: s, open_ended: Some(n), .. } => roll_open_ended(s, n, values, rng),
+            DieRollTerm::DieRoll { sides: s, compounding: true, .. } => {
+                let mut face = rng.gen_range(1, s as i32 + 1);
+                let mut total = face;
+                let mut draws = 1;
+                for _ in 0..MAX_EXPLOSIONS {
+                    if face != s as i32 {
+                        break;
+                    }
+                    face = rng.gen_range(1, s as i32 + 1);
+                    total += face;
+                    draws += 1;
+                }
+                values.push(total);
+                return draws;
+            }
+            DieRollTerm::DieRoll { sides: s, exploding: true, .. } => {
+                let mut face = rng.gen_range(1, s as i32 + 1);
+                values.push(face);
+                for _ in 0..MAX_EXPLOSIONS {
+                    if face != s as i32 {
+                        break;
+                    }
+                    face = rng.gen_range(1, s as i32 + 1);
+                    values.push(face);
+                }
+            }
+            DieRollTerm::DieRoll { sides: s, reroll, .. } => {
+                let face = rng.gen_range(1, s as i32 + 1);
+                values.push(face);
+                if reroll.is_some_and(|c| c.matches(face)) {
+                    values.push(rng.gen_range(1, s as i32 + 1));
+                }
+            }
+        }
+        values.len() - before
+    }
+}
+
+/// The maximum number of extra dice a single die may add to a roll by exploding, which keeps
+/// a streak of lucky rolls (or a degenerate die) from rolling forever.
+pub const MAX_EXPLOSIONS: usize = 100;
+
+/// Parses a number from a die roll term, which the term pattern guarantees to be well-formed, so
+/// the only way it can fail is by being too large.
+fn number<T: FromStr>(s: &str) -> Result<T, RollError> {
+    s.parse::<T>().map_err(|_| RollError::Overflow)
+}
+
+/// Parses the number of dice written before the `d` of a die roll term, which is one die when
+/// left out (`d20`, `+d4`, `-d6`).
+fn dice_count(s: &str) -> Result<i16, RollError> {
+    match s {
+        "" | "+" => Ok(1),
+        "-" => Ok(-1),
+        n => number(n),
+    }
+}
+
+/// Splits `s` into its leading run of digits and whatever follows it.
+fn split_number(s: &str) -> (&str, &str) {
+    s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()))
+}
+
+/// Splits `s` into a leading comparison such as `<=3` and whatever follows it.
+fn split_comparison(s: &str) -> (&str, &str) {
+    let op = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, _) = split_number(&s[op..]);
+    s.split_at(op + digits.len())
+}
+
+fn default_open_threshold(sides: u16) -> u16 {
+    cmp::max(1, sides / 20)
+}
+
+/// Rolls a single open-ended die, appending every roll in its chain to `values`. A roll within
+/// `n` faces of the top is followed by another roll that is added; a first roll within `n` faces
+/// of the bottom is followed by another roll that is subtracted (recorded as a negative value).
+/// The chain continues in the same direction for as long as the follow-up rolls land at the top,
+/// up to `MAX_EXPLOSIONS` extra rolls.
+fn roll_open_ended<R: Rng>(sides: u16, n: u16, values: &mut Vec<i32>, rng: &mut R) {
+    let mut roll = || rng.gen_range(1, sides as i32 + 1);
+    let high = sides as i32 - n as i32;
+    let first = roll();
+    values.push(first);
+
+    let direction = if first > high {
+        1
+    } else if first <= n as i32 {
+        -1
+    } else {
+        return;
+    };
+    for _ in 0..MAX_EXPLOSIONS {
+        let next = roll();
+        values.push(next * direction);
+        if next <= high {
+            break;
+        }
+    }
+}
+
+/// Formats an individual die roll term in a human-friendly fashion. For `Modifier` terms,
+/// this will force the printing of a + or - sign before the modifier value. For `DieRoll`
+/// terms, this displays the term in the form `5d10`, and custom dice in the form `2d[1,3,5]`.
+impl fmt::Display for DieRollTerm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DieRollTerm::Modifier(n) => write!(f, "{:+}", n),
+            DieRollTerm::Fudge { multiplier: m } => write!(f, "{}dF", m),
+            DieRollTerm::Custom { multiplier: m, ref faces } => {
+                let faces: Vec<String> = faces.iter().map(|f| f.to_string()).collect();
+                write!(f, "{}d[{}]", m, faces.join(","))
+            }
+            DieRollTerm::DieRoll {
+                multiplier: m,
+                sides: s,
+                open_ended,
+                exploding,
+                compounding,
+                penetrating,
+                reroll,
+                min,
+                max,
+                keep,
+                success,
+            } => {
+                write!(f, "{}d{}", m, s)?;
+                match open_ended {
+                    Some(n) if n == default_open_threshold(s) => write!(f, "open")?,
+                    Some(n) => write!(f, "open{}", n)?,
+                    None => {}
+                }
+                if exploding {
+                    write!(f, "!")?;
+                }
+                if compounding {
+                    write!(f, "!")?;
+                }
+                if penetrating {
+                    write!(f, "p")?;
+                }
+                match reroll {
+                    Some(Comparison::AtMost(n)) => write!(f, "r{}", n)?,
+                    Some(c) => write!(f, "ro{}", c)?,
+                    None => {}
+                }
+                if let Some(n) = min {
+                    write!(f, "min{}", n)?;
+                }
+                if let Some(n) = max {
+                    write!(f, "max{}", n)?;
+                }
+                if let Some(k) = keep {
+                    write!(f, "{}", k)?;
+                }
+                match success {
+                    Some(c) => write!(f, "{}", c),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// Parses a single die roll term such as `3d6`, `-2` or `4d6kh3`. The whole string must be one
+/// term, so `2d6+1` is an error; whitespace is ignored as it is by `roll_dice()`.
+///
+/// ```rust
+/// # extern crate d20;
+/// # use d20::DieRollTerm;
+/// # fn main() {
+///     match "3d6".parse::<DieRollTerm>().unwrap() {
+///         DieRollTerm::DieRoll { multiplier, sides, .. } => assert_eq!((multiplier, sides), (3, 6)),
+///         _ => unreachable!(),
+///     }
+///     assert!("chickens".parse::<DieRollTerm>().is_err());
+/// # }
+/// ```
+impl FromStr for DieRollTerm {
+    type Err = RollError;
+
+    fn from_str(s: &str) -> Result<DieRollTerm, RollError> {
+        let (drt, offsets) = strip_whitespace(s);
+        if drt.is_empty() {
+            return Err(RollError::EmptyExpression);
+        }
+        match term_len(&drt) {
+            Some(end) if end == drt.len() => {}
+            end => {
+                let at = end.unwrap_or(0);
+                return Err(RollError::ParseFailure { position: offsets[at], fragment: drt[at..].to_string() });
+            }
+        }
+        let term = DieRollTerm::try_parse(&drt)?;
+        term.check()?;
+        Ok(term)
+    }
+}
+
+/// A comparison deciding whether a single die counts as a success in a dice pool, written
+/// after a die roll term as in `6d10>=7`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Comparison {
+    /// The die succeeds on this face or higher (`>=`)
+    AtLeast(i32),
+    /// The die succeeds above this face (`>`)
+    GreaterThan(i32),
+    /// The die succeeds on this face or lower (`<=`)
+    AtMost(i32),
+    /// The die succeeds below this face (`<`)
+    LessThan(i32),
+    /// The die succeeds on exactly this face (`=`)
+    Equal(i32),
+}
+
+impl Comparison {
+    fn parse(c: &str) -> Result<Comparison, RollError> {
+        let (op, n) = c.split_at(c.find(|ch: char| ch.is_ascii_digit()).unwrap_or(c.len()));
+        let n = number(n)?;
+        Ok(match op {
+            ">=" => Comparison::AtLeast(n),
+            ">" => Comparison::GreaterThan(n),
+            "<=" => Comparison::AtMost(n),
+            "<" => Comparison::LessThan(n),
+            _ => Comparison::Equal(n),
+        })
+    }
+
+    /// Returns whether a die showing `face` satisfies the comparison.
+    pub fn matches(&self, face: i32) -> bool {
+        match *self {
+            Comparison::AtLeast(n) => face >= n,
+            Comparison::GreaterThan(n) => face > n,
+            Comparison::AtMost(n) => face <= n,
+            Comparison::LessThan(n) => face < n,
+            Comparison::Equal(n) => face == n,
+        }
+    }
+
+    /// The probability that a die settling on each face with the probabilities in `dist`
+    /// (indexed from a face of 1) satisfies the comparison.
+    fn probability(&self, dist: &[f64]) -> f64 {
+        dist.iter().enumerate().filter(|&(i, _)| self.matches(i as i32 + 1)).map(|(_, p)| p).sum()
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Comparison::AtLeast(n) => write!(f, ">={}", n),
+            Comparison::GreaterThan(n) => write!(f, ">{}", n),
+            Comparison::AtMost(n) => write!(f, "<={}", n),
+            Comparison::LessThan(n) => write!(f, "<{}", n),
+            Comparison::Equal(n) => write!(f, "={}", n),
+        }
+    }
+}
+
+/// Which of the dice rolled for a term count towards its result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Keep {
+    /// Keep this many of the highest dice, as in `2d20kh1` for advantage
+    Highest(u16),
+    /// Keep this many of the lowest dice, as in `2d20kl1` for disadvantage
+    Lowest(u16),
+}
+
+impl Keep {
+    /// Returns how many dice are kept.
+    pub fn count(&self) -> u16 {
+        match *self {
+            Keep::Highest(n) | Keep::Lowest(n) => n,
+        }
+    }
+
+    /// Returns whether each of the dice with the given `results` is kept. Ties are broken in
+    /// favour of the dice rolled first.
+    fn select(&self, results: &[i32]) -> Vec<bool> {
+        let mut order: Vec<usize> = (0..results.len()).collect();
+        let n = match *self {
+            Keep::Highest(n) => {
+                order.sort_by_key(|&i| cmp::Reverse(results[i]));
+                n
+            }
+            Keep::Lowest(n) => {
+                order.sort_by_key(|&i| results[i]);
+                n
+            }
+        };
+        let mut kept = vec![false; results.len()];
+        for &i in order.iter().take(n as usize) {
+            kept[i] = true;
+        }
+        kept
+    }
+}
+
+impl fmt::Display for Keep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Keep::Highest(n) => write!(f, "kh{}", n),
+            Keep::Lowest(n) => write!(f, "kl{}", n),
+        }
+    }
+}
+
+/// The reasons a die roll expression, or a request made about one, can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum RollError {
+    /// The expression was empty or contained only whitespace
+    EmptyExpression,
+    /// The expression, or part of it, could not be parsed into die roll terms
+    ParseFailure {
+        /// The byte offset into the expression, as given, where parsing failed
+        position: usize,
+        /// The text that could not be parsed, from `position` to the end of the expression with
+        /// whitespace removed, or empty if the expression ended too soon
+        fragment: String,
+    },
+    /// A die roll term was well-formed but cannot be rolled, such as `1d0` or `2d6kh3`
+    InvalidTerm {
+        /// The offending term
+        term: String,
+        /// Why the term cannot be rolled
+        reason: &'static str,
+    },
+    /// A range's minimum was greater than its maximum
+    InvalidRange {
+        /// The requested minimum
+        min: i32,
+        /// The requested maximum
+        max: i32,
+    },
+    /// A number in the expression was too large to represent
+    Overflow,
+    /// Rolling the expression needed more dice than the work budget allows
+    BudgetExceeded,
+    /// The expression divided by zero, such as `1d6/0` or `1d6/1dF` when the Fudge die shows 0
+    DivisionByZero,
+    /// The expression opened a parenthesis without closing it, or closed one it never opened
+    UnbalancedParentheses,
+    /// The expression referenced a variable that was not given a value
+    UndefinedVariable {
+        /// The name of the variable
+        name: String,
+    },
+    /// The expression has too many possible outcomes to enumerate them all exactly
+    TooLarge,
+    /// An argument other than the expression was outside its allowed range
+    InvalidArgument(&'static str),
+    /// The expression is valid but cannot be used for the requested operation, such as the
+    /// expected successes of an expression without a dice pool
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for RollError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RollError::EmptyExpression => write!(f, "Invalid die roll expression: no die roll terms found."),
+            RollError::ParseFailure { position, ref fragment } if fragment.is_empty() => {
+                write!(f, "Invalid die roll expression: unexpected end at position {}.", position)
+            }
+            RollError::ParseFailure { position, ref fragment } => {
+                write!(f, "Invalid die roll expression: could not parse '{}' at position {}.", fragment, position)
+            }
+            RollError::InvalidTerm { ref term, reason } => {
+                write!(f, "Invalid die roll term '{}': {}.", term, reason)
+            }
+            RollError::InvalidRange { min, max } => {
+                write!(f, "Invalid range: min ({}) must be less than or equal to max ({}).", min, max)
+            }
+            RollError::Overflow => write!(f, "Invalid die roll expression: a number is too large."),
+            RollError::BudgetExceeded => write!(f, "Invalid die roll expression: work budget exceeded."),
+            RollError::DivisionByZero => write!(f, "Invalid die roll expression: division by zero."),
+            RollError::TooLarge => {
+                write!(f, "Die roll expression has too many outcomes to compute exactly.")
+            }
+            RollError::UnbalancedParentheses => {
+                write!(f, "Invalid die roll expression: unbalanced parentheses.")
+            }
+            RollError::UndefinedVariable { ref name } => {
+                write!(f, "Invalid die roll expression: undefined variable '{}'.", name)
+            }
+            RollError::InvalidArgument(reason) => write!(f, "Invalid argument: {}.", reason),
+            RollError::Unsupported(reason) => write!(f, "Unsupported die roll expression: {}.", reason),
+        }
+    }
+}
+
+impl Error for RollError {}
+
+/// Evaluates the expression string input as a die roll expression (e.g. 3d6 + 4). The
+/// results are returned in a `Result` object that contains either a valid `Roll` or a
+/// `RollError` describing why the function was unable to roll the dice / evaluate the expression.
+///
+/// The whole expression must be understood: apart from whitespace and a trailing label, any
+/// text that is not part of a term is an error, so `3d6 chicken` fails rather than rolling 3d6.
+#[cfg(feature = "std")]
+pub fn roll_dice(s: &str) -> Result<Roll, RollError> {
+    roll_dice_with(s, &mut thread_rng())
+}
+
+/// Evaluates a die roll expression like `roll_dice()`, drawing every die from `rng` instead of
+/// the thread-local generator. Any `rand::Rng` implementation can be used, such as a seeded or
+/// counter-based generator for reproducible simulations.
+///
+/// ```rust
+/// # extern crate d20;
+/// # extern crate rand;
+/// # use rand::{SeedableRng, XorShiftRng};
+/// # fn main() {
+///     let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+///     let roll = d20::roll_dice_with("3d6 + 4", &mut rng).unwrap();
+///     assert!(roll.total >= 7 && roll.total <= 22);
+/// # }
+/// ```
+pub fn roll_dice_with<R: Rng>(s: &str, rng: &mut R) -> Result<Roll, RollError> {
+    roll_expression(s, usize::MAX, rng)
+}
+
+/// Evaluates a die roll expression like `roll_dice()`, drawing the dice from a random number
+/// generator seeded with `seed`. The same expression and seed always produce the same roll, which
+/// makes results reproducible for tests and replays.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let first = d20::roll_dice_seeded("4d6kh3 + 2", 42).unwrap();
+///     let again = d20::roll_dice_seeded("4d6kh3 + 2", 42).unwrap();
+///     assert_eq!(first.total, again.total);
+///     assert_eq!(first.to_string(), again.to_string());
+/// # }
+/// ```
+pub fn roll_dice_seeded(s: &str, seed: u64) -> Result<Roll, RollError> {
+    roll_dice_with(s, &mut Isaac64Rng::from_seed(&[seed][..]))
+}
+
+/// Evaluates a die roll expression like `roll_dice()`, under the limits given in `options`.
+///
+/// ```rust
+/// # extern crate d20;
+/// # use d20::RollOptions;
+/// # fn main() {
+///     let options = RollOptions { work_budget: 10 };
+///     assert!(d20::roll_dice_with_options("3d6 + 4", options).is_ok());
+///     assert!(d20::roll_dice_with_options("11d6", options).is_err());
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn roll_dice_with_options(s: &str, options: RollOptions) -> Result<Roll, RollError> {
+    roll_expression(s, options.work_budget, &mut thread_rng())
+}
+
+/// Limits applied by `roll_dice_with_options()`, protecting callers that roll untrusted
+/// expressions from ones that would take unreasonably long to evaluate.
+#[derive(Debug, Clone, Copy)]
+pub struct RollOptions {
+    /// The most dice the whole expression may roll, counting every die drawn from the random
+    /// number generator including explosions and rerolls. Rolling stops with an error as soon
+    /// as the budget is exceeded. Defaults to `DEFAULT_WORK_BUDGET`.
+    pub work_budget: usize,
+}
+
+/// The default `RollOptions::work_budget`: enough for any reasonable expression.
+pub const DEFAULT_WORK_BUDGET: usize = 10_000;
+
+impl Default for RollOptions {
+    fn default() -> RollOptions {
+        RollOptions { work_budget: DEFAULT_WORK_BUDGET }
+    }
+}
+
+/// Evaluates a die roll expression whose dice counts or modifiers are given by named variables,
+/// written in braces (e.g. `{cantrip_dice}d10`). Each variable is replaced by its value from
+/// `vars` before the expression is parsed, so the resolved expression is what appears in the
+/// `drex` of the resulting `Roll`. A negative value is substituted in parentheses, so
+/// `1d6-{penalty}` with a `penalty` of -2 resolves to `1d6-(-2)`.
+///
+/// Referring to a variable that is not defined in `vars`, or leaving a brace unterminated, is an
+/// error.
+///
+/// ```rust
+/// # extern crate d20;
+/// # use std::collections::HashMap;
+/// # fn main() {
+///     let mut vars = HashMap::new();
+///     vars.insert("cantrip_dice", 2);
+///     let r = d20::roll_dice_with_vars("{cantrip_dice}d10", &vars).unwrap();
+///     assert_eq!(r.drex, "2d10");
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn roll_dice_with_vars(s: &str, vars: &HashMap<&str, i32>) -> Result<Roll, RollError> {
+    let mut resolved = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(len) => start + len,
+            None => {
+                return Err(RollError::ParseFailure {
+                    position: s.len() - rest.len() + start,
+                    fragment: rest[start..].split_whitespace().collect(),
+                })
+            }
+        };
+        let name = rest[start + 1..end].trim();
+        let value = match vars.get(name) {
+            Some(value) => value,
+            None => return Err(RollError::UndefinedVariable { name: name.to_string() }),
+        };
+        resolved.push_str(&rest[..start]);
+        if *value < 0 {
+            // `1d6-{x}` must not resolve to `1d6--2`
+            resolved.push_str(&format!("({})", value));
+        } else {
+            resolved.push_str(&value.to_string());
+        }
+        rest = &rest[end + 1..];
+    }
+    resolved.push_str(rest);
+
+    roll_expression(&resolved, usize::MAX, &mut thread_rng())
+}
+
+fn roll_expression<R: Rng>(s: &str, work_budget: usize, rng: &mut R) -> Result<Roll, RollError> {
+    RollExpr::parse(s)?.roll_within(work_budget, rng)
+}
+
+/// Splits the label off the end of a die roll expression (e.g. `1d20+5 [to hit]`), returning the
+/// expression and the text between the brackets, whitespace included.
+fn split_label(s: &str) -> (&str, Option<String>) {
+    let trimmed = s.trim_end();
+    if trimmed.ends_with(']') {
+        if let Some(start) = trimmed.rfind('[') {
+            // the faces of a custom die such as `d[2,4,6]` are not a label
+            if trimmed[..start].trim_end().ends_with(['d', 'D']) {
+                return (s, None);
+            }
+            return (&s[..start], Some(trimmed[start + 1..trimmed.len() - 1].to_string()));
+        }
+    }
+    (s, None)
+}
+
+/// Returns the signed result of each of the evaluated terms `values`.
+fn subtotals(values: &[(DieRollTerm, Vec<i32>)]) -> Vec<i64> {
+    values.iter().map(|v| v.0.sum(&v.1)).collect()
+}
+
+/// Rolls an attack expression containing a single d20 (e.g. `1d20+7`), returning the roll
+/// and whether it is a critical hit. An attack is a critical hit when the natural d20 shows
+/// `crit_min` or higher, so a weapon that crits on 19-20 uses a `crit_min` of 19.
+///
+/// `crit_min` must be between 2 and 20, and the expression must roll exactly one d20.
+#[cfg(feature = "std")]
+pub fn roll_attack_crit_range(s: &str, crit_min: u8) -> Result<(Roll, bool), RollError> {
+    if !(2..=20).contains(&crit_min) {
+        return Err(RollError::InvalidArgument("crit_min must be between 2 and 20"));
+    }
+    let r = roll_dice(s)?;
+
+    match r.natural_d20() {
+        Some(face) => {
+            let crit = face >= crit_min as i32;
+            Ok((r, crit))
+        }
+        None => Err(RollError::Unsupported("expected exactly one d20")),
+    }
+}
+
+/// Rolls a d20 check (`1d20` plus `modifier`) against a difficulty class `dc`, using the
+/// classic natural 20 and natural 1 rules. See `d20_check_with_options()` for how the result
+/// is decided.
+///
+/// ```rust
+/// # extern crate d20;
+/// # use d20::CheckResult;
+/// # fn main() {
+///     let check = d20::d20_check(5, 15);
+///     assert!(check.roll.total >= 6 && check.roll.total <= 25);
+///     if check.roll.total >= 15 && check.result != CheckResult::CriticalFailure {
+///         assert!(check.result.is_success());
+///     }
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn d20_check(modifier: i32, dc: i32) -> CheckRoll {
+    d20_check_with_options(modifier, dc, CheckOptions::default())
+}
+
+/// Rolls a d20 check (`1d20` plus `modifier`) against a difficulty class `dc`. The result is
+/// decided in this order:
+///
+/// 1. a natural roll of `options.fumble_max` or lower is a `CriticalFailure`, whatever the total;
+/// 2. a natural roll of `options.crit_min` or higher is a `CriticalSuccess`, whatever the total;
+/// 3. otherwise a total of `dc` or more is a `Success`, and anything less a `Failure`.
+#[cfg(feature = "std")]
+pub fn d20_check_with_options(modifier: i32, dc: i32, options: CheckOptions) -> CheckRoll {
+    let drex = if modifier == 0 { "1d20".to_string() } else { format!("1d20{:+}", modifier) };
+    let roll = roll_expression(&drex, usize::MAX, &mut thread_rng()).expect("a d20 check is a valid expression");
+    let natural = roll.natural_d20().expect("a d20 check rolls exactly one d20");
+
+    let result = if natural <= options.fumble_max as i32 {
+        CheckResult::CriticalFailure
+    } else if natural >= options.crit_min as i32 {
+        CheckResult::CriticalSuccess
+    } else if roll.total >= i64::from(dc) {
+        CheckResult::Success
+    } else {
+        CheckResult::Failure
+    };
+    CheckRoll { roll, result }
+}
+
+/// The critical rules applied by `d20_check_with_options()`.
+#[derive(Debug, Clone, Copy)]
+pub struct CheckOptions {
+    /// The lowest natural roll that is a critical success. Defaults to 20; 21 or more turns
+    /// critical successes off.
+    pub crit_min: u8,
+    /// The highest natural roll that is a critical failure. Defaults to 1; 0 turns critical
+    /// failures off.
+    pub fumble_max: u8,
+}
+
+impl Default for CheckOptions {
+    fn default() -> CheckOptions {
+        CheckOptions { crit_min: 20, fumble_max: 1 }
+    }
+}
+
+/// The degree of success of a d20 check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CheckResult {
+    /// A natural roll in the critical range, succeeding whatever the total
+    CriticalSuccess,
+    /// A total that met or beat the difficulty class
+    Success,
+    /// A total that fell short of the difficulty class
+    Failure,
+    /// A natural roll in the fumble range, failing whatever the total
+    CriticalFailure,
+}
+
+impl CheckResult {
+    /// Returns whether the check succeeded, critically or not.
+    pub fn is_success(&self) -> bool {
+        *self == CheckResult::CriticalSuccess || *self == CheckResult::Success
+    }
+}
+
+/// The result of a d20 check made with `d20_check()`.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CheckRoll {
+    /// The check as rolled
+    pub roll: Roll,
+    /// The degree of success of the check
+    pub result: CheckResult,
+}
+
+/// Evaluates a die roll expression and returns an iterator over exactly `n` rolls of it. This
+/// is the bounded counterpart of converting a `Roll` into an iterator, which never ends on its
+/// own and must be constrained with `take(n)`.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let rolls: Vec<d20::Roll> = d20::roll_times("3d6", 6).unwrap().collect::<Result<_, _>>().unwrap();
+///     assert_eq!(rolls.len(), 6);
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn roll_times(s: &str, n: usize) -> Result<iter::Take<RollIterator>, RollError> {
+    RollExpr::parse(s).map(|e| e.into_iter().take(n))
+}
+
+/// Evaluates several die roll expressions written together, separated by `;` or `,`, as in
+/// `1d20+5; 2d6+3` for an attack and its damage. Each expression is rolled on its own, and the
+/// rolls are returned in order as a `MultiRoll`. Separators within brackets, such as between the faces of a
+/// custom die, do not split the expression. An empty expression between separators is an
+/// error, as is any invalid expression.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let rolls = d20::roll_dice_multi("1d20+5; 2d6+3").unwrap();
+///     assert_eq!(rolls.len(), 2);
+///     assert!(rolls[1].total >= 5 && rolls[1].total <= 15);
+///     assert!(d20::roll_dice_multi("1d20+5;;2d6").is_err());
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn roll_dice_multi(s: &str) -> Result<MultiRoll, RollError> {
+    // separators within brackets belong to custom dice or labels, as in `1d[1,3,5]; 1d6`
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut exprs = Vec::new();
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ';' | ',' if depth == 0 => {
+                exprs.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    exprs.push(&s[start..]);
+    exprs.into_iter().map(roll_dice).collect::<Result<_, _>>().map(MultiRoll)
+}
+
+/// The rolls of several die roll expressions written together, as returned by
+/// `roll_dice_multi()`. It dereferences to the rolls, in the order they were written.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiRoll(pub Vec<Roll>);
+
+#[cfg(feature = "std")]
+impl MultiRoll {
+    /// Returns the sum of the totals of all of the rolls.
+    pub fn grand_total(&self) -> i64 {
+        self.0.iter().map(|r| r.total).sum()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Deref for MultiRoll {
+    type Target = [Roll];
+
+    fn deref(&self) -> &[Roll] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl IntoIterator for MultiRoll {
+    type Item = Roll;
+    type IntoIter = vec::IntoIter<Roll>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Displays each roll on its own line, as a `Roll` is displayed, followed by the sum of their
+/// totals, as in:
+///
+/// ```text
+/// 1d20[14]+5 [attack] (Total: 19)
+/// 2d6[3, 5]+3 [damage] (Total: 11)
+/// (Grand Total: 30)
+/// ```
+#[cfg(feature = "std")]
+impl fmt::Display for MultiRoll {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for r in &self.0 {
+            writeln!(f, "{}", r)?;
+        }
+        write!(f, "(Grand Total: {})", self.grand_total())
+    }
+}
+
+/// Evaluates each of a batch of die roll expressions independently, returning the result of each
+/// in the same order. An invalid expression only fails its own entry; see `roll_all_strict()`
+/// to fail the whole batch instead.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let results = d20::roll_all(&["1d20+2", "chickens", "1d20-1"]);
+///     assert!(results[0].is_ok() && results[1].is_err() && results[2].is_ok());
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn roll_all(exprs: &[&str]) -> Vec<Result<Roll, RollError>> {
+    exprs.iter().map(|s| roll_dice(s)).collect()
+}
+
+/// Evaluates each of a batch of die roll expressions like `roll_all()`, but fails with the error
+/// of the first invalid expression, without rolling the ones after it.
+#[cfg(feature = "std")]
+pub fn roll_all_strict(exprs: &[&str]) -> Result<Vec<Roll>, RollError> {
+    exprs.iter().map(|s| roll_dice(s)).collect()
+}
+
+/// Rolls a die roll expression `n` times, returning only the totals. The expression is parsed
+/// once and no `Roll` is built for the individual rolls, which makes this considerably cheaper
+/// than collecting `n` rolls from an iterator when only the results matter (e.g. simulations):
+/// over twice as fast for `4d6kh3+2d8-1` in the `totals` benchmark.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let totals = d20::roll_totals("3d6", 1000).unwrap();
+///     assert_eq!(totals.len(), 1000);
+///     assert!(totals.iter().all(|&t| t >= 3 && t <= 18));
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn roll_totals(s: &str, n: usize) -> Result<Vec<i64>, RollError> {
+    totals(s, n)?.collect()
+}
+
+/// Rolls a die roll expression `n` times and returns the average of the totals, an empirical
+/// counterpart to the exact `mean()`. Like `roll_totals()`, the expression is parsed only once.
+/// Averaging no rolls at all is an error.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     assert_eq!(d20::average("1d1 + 2", 10).unwrap(), 3.0);
+///     let avg = d20::average("1d6", 1000).unwrap();
+///     assert!(avg >= 1.0 && avg <= 6.0);
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn average(s: &str, n: usize) -> Result<f64, RollError> {
+    if n == 0 {
+        return Err(RollError::InvalidArgument("n must be at least 1 to average rolls"));
+    }
+    let mut sum = 0.0;
+    for total in totals(s, n)? {
+        sum += total? as f64;
+    }
+    Ok(sum / n as f64)
+}
+
+/// Rolls a die roll expression `samples` times and counts how often each total came up, giving a
+/// sampled picture of the distribution of the expression. Like `roll_totals()`, the expression is
+/// parsed only once. Totals that never came up are left out of the map.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let counts = d20::distribution("2d6", 1000).unwrap();
+///     assert_eq!(counts.values().sum::<u32>(), 1000);
+///     assert!(counts.keys().all(|&t| t >= 2 && t <= 12));
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn distribution(s: &str, samples: usize) -> Result<BTreeMap<i64, u32>, RollError> {
+    let mut counts = BTreeMap::new();
+    for total in totals(s, samples)? {
+        *counts.entry(total?).or_insert(0) += 1;
+    }
+    Ok(counts)
+}
+
+/// Parses a die roll expression once and returns an iterator rolling it `n` times, yielding only
+/// the totals.
+#[cfg(feature = "std")]
+fn totals(s: &str, n: usize) -> Result<impl Iterator<Item = Result<i64, RollError>>, RollError> {
+    let (_, terms, expr) = parse_input(s)?;
+
+    let mut rng = thread_rng();
+    let mut faces = Vec::new();
+    Ok((0..n).map(move |_| {
+        let subtotals: Vec<i64> = terms
+            .iter()
+            .map(|t| {
+                faces.clear();
+                t.roll_into(&mut faces, &mut rng);
+                t.sum(&faces)
+            })
+            .collect();
+        expr.evaluate(&subtotals)
+    }))
+}
+
+/// Rolls a single die with `sides` sides `samples` times and counts how often each face came up,
+/// for checking that the dice are fair. Faces that never came up are left out of the map, and
+/// no samples produce an empty map.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let freq = d20::face_frequencies(6, 600).unwrap();
+///     assert_eq!(freq.values().sum::<u32>(), 600);
+///     assert!(freq.keys().all(|&face| face >= 1 && face <= 6));
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn face_frequencies(sides: u16, samples: u32) -> Result<BTreeMap<u16, u32>, RollError> {
+    let die = DieRollTerm::DieRoll {
+        multiplier: 1,
+        sides,
+        open_ended: None,
+        exploding: false,
+        compounding: false,
+        penetrating: false,
+        reroll: None,
+        min: None,
+        max: None,
+        keep: None,
+        success: None,
+    };
+    die.check()?;
+
+    let mut rng = thread_rng();
+    let mut freq = BTreeMap::new();
+    let mut faces = Vec::new();
+    for _ in 0..samples {
+        faces.clear();
+        die.roll_into(&mut faces, &mut rng);
+        *freq.entry(faces[0] as u16).or_insert(0) += 1;
+    }
+    Ok(freq)
+}
+
+/// Rolls a die roll expression as a dice pool, counting the dice that the `is_success` closure
+/// deems a success. This is the programmable escape hatch for pool systems whose success rules
+/// go beyond a simple target number.
+///
+/// The closure is applied to every face rolled by a `DieRoll` term; modifier terms are ignored.
+/// The full `Roll` is returned along with the number of successes.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     // count every even face in a pool of six d10s
+///     let (roll, successes) = d20::roll_pool_with("6d10", |face| face % 2 == 0).unwrap();
+///     assert_eq!(roll.values[0].1.len(), 6);
+///     assert!(successes <= 6);
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn roll_pool_with<F>(s: &str, is_success: F) -> Result<(Roll, usize), RollError>
+    where F: Fn(i32) -> bool
+{
+    roll_pool_with_options(s, is_success, PoolOptions::default()).map(|p| (p.roll, p.successes))
+}
 
-        Ok(Roll {
-            drex: s,
-            values: v,
-            total: t.into_iter().fold(0i32, |sum, val| sum + DieRollTerm::calculate(val)),
+/// Rolls a dice pool like `roll_pool_with()`, applying the pool-wide rules in `options`.
+///
+/// With `reroll_pool_on_zero` set, a pool that scores no successes is rolled again in its
+/// entirety and the second result stands. This happens at most once, regardless of how the
+/// second roll turns out, and is independent of any per-die rerolls.
+#[cfg(feature = "std")]
+pub fn roll_pool_with_options<F>(s: &str, is_success: F, options: PoolOptions)
+    -> Result<PoolRoll, RollError>
+    where F: Fn(i32) -> bool
+{
+    let mut roll = roll_dice(s)?;
+    let mut successes = count_successes(&roll, &is_success);
+    let mut pool_rerolled = false;
+
+    if successes == 0 && options.reroll_pool_on_zero {
+        roll = roll_dice(s)?;
+        successes = count_successes(&roll, &is_success);
+        pool_rerolled = true;
+    }
+
+    Ok(PoolRoll { roll, successes, pool_rerolled })
+}
+
+/// Pool-wide rules applied by `roll_pool_with_options()`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PoolOptions {
+    /// Reroll the entire pool once if the first roll yields zero successes.
+    pub reroll_pool_on_zero: bool,
+}
+
+/// The result of rolling a dice pool with `roll_pool_with_options()`.
+#[derive(Debug)]
+pub struct PoolRoll {
+    /// The roll that stands, i.e. the reroll if the pool was rerolled
+    pub roll: Roll,
+    /// The number of dice in `roll` counted as successes
+    pub successes: usize,
+    /// Whether the whole pool was rerolled after scoring no successes
+    pub pool_rerolled: bool,
+}
+
+#[cfg(feature = "std")]
+fn count_successes<F>(r: &Roll, is_success: &F) -> usize
+    where F: Fn(i32) -> bool
+{
+    r.dice_values()
+        .flat_map(|v| v.0.faces(&v.1))
+        .filter(|&face| is_success(face))
+        .count()
+}
+
+/// Rolls a score for each of the named abilities using `4d6kh3` (four d6, keeping the highest
+/// three), pairing every roll with its ability name in the order given.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let scores = d20::roll_ability_scores(&["STR", "DEX", "CON", "INT", "WIS", "CHA"]).unwrap();
+///     assert_eq!(scores.len(), 6);
+///     assert_eq!(scores[0].0, "STR");
+///     assert!(scores[0].1.total >= 3 && scores[0].1.total <= 18);
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn roll_ability_scores(names: &[&str]) -> Result<Vec<(String, Roll)>, RollError> {
+    roll_ability_scores_with(names, "4d6kh3", false)
+}
+
+/// Rolls a score for each of the named abilities using the given die roll expression, for
+/// systems that generate ability scores differently. When `best_first` is set, the rolls are
+/// sorted from highest to lowest total before being paired with the names, so the abilities
+/// listed first receive the best scores.
+#[cfg(feature = "std")]
+pub fn roll_ability_scores_with(names: &[&str], s: &str, best_first: bool)
+    -> Result<Vec<(String, Roll)>, RollError>
+{
+    let mut rolls = Vec::with_capacity(names.len());
+    for _ in names {
+        rolls.push(roll_dice(s)?);
+    }
+    if best_first {
+        rolls.sort_by_key(|r| cmp::Reverse(r.total));
+    }
+
+    Ok(names.iter().map(|n| n.to_string()).zip(rolls).collect())
+}
+
+/// Computes the expected number of successes rolled by the dice pools in a die roll expression
+/// such as `8d10>=7`, without rolling any dice. Each die of a pool contributes the probability
+/// that it meets the pool's success condition, so `8d10>=7` expects `8 × 0.4 = 3.2` successes.
+/// Several pools in one expression are summed, with subtracted pools counting against the result.
+///
+/// Terms that are not dice pools are ignored; an expression without any dice pool is an error.
+pub fn expected_successes(s: &str) -> Result<f64, RollError> {
+    let terms = parse_terms(s)?;
+
+    let mut pools = Vec::new();
+    for t in &terms {
+        match *t {
+            DieRollTerm::DieRoll { success: Some(_), .. } if t.explodes() => {
+                return Err(RollError::Unsupported("exploding dice have no exact expectation"));
+            }
+            DieRollTerm::DieRoll { keep: Some(_), success: Some(_), .. } => {
+                let (offset, dist) = t.keep_distribution()?;
+                pools.push(dist.iter().enumerate().map(|(i, p)| (offset + i as i64) as f64 * p).sum());
+            }
+            DieRollTerm::DieRoll { multiplier: m, success: Some(c), .. } => {
+                pools.push(f64::from(m) * c.probability(&t.face_distribution()));
+            }
+            _ => {}
+        }
+    }
+
+    if pools.is_empty() {
+        Err(RollError::Unsupported("no dice pool found"))
+    } else {
+        Ok(pools.iter().sum())
+    }
+}
+
+/// Returns how many dice with `sides` sides are needed for their average total to reach
+/// `target_avg`: the smallest `n` for which `n * (sides + 1) / 2 >= target_avg`. Partial dice
+/// round up to the next whole die, so averaging 30 on d6s takes 9 dice rather than 8.57.
+///
+/// # Panics
+///
+/// Panics if `sides` is 0, as no number of zero-sided dice averages anything.
+#[cfg(feature = "std")]
+pub fn dice_for_target_average(sides: u16, target_avg: f64) -> u32 {
+    assert!(sides > 0, "a die must have at least one side");
+    if target_avg <= 0.0 {
+        return 0;
+    }
+    (2.0 * target_avg / (f64::from(sides) + 1.0)).ceil() as u32
+}
+
+/// Returns the largest face any single die in a die roll expression can show, without rolling.
+/// For `2d6+1d12` this is 12, and a die lowered to a maximum, as in `1d6max4`, counts that
+/// maximum. Expressions without dice are an error.
+pub fn max_single_die_value(s: &str) -> Result<i32, RollError> {
+    let (_, terms, _) = parse_input(s)?;
+
+    terms.iter()
+        .filter_map(|t| match *t {
+            DieRollTerm::DieRoll { sides, .. } => Some(t.clamp(i32::from(sides))),
+            DieRollTerm::Fudge { .. } => Some(1),
+            DieRollTerm::Custom { ref faces, .. } => faces.iter().max().cloned(),
+            DieRollTerm::Modifier(_) => None,
         })
+        .max()
+        .ok_or(RollError::Unsupported("no dice found"))
+}
+
+/// Determines whether two die roll expressions are equivalent, e.g. `2d6+3d6` and `5d6`, or
+/// `+2+3` and `+5`. Both expressions are normalized by folding all modifiers into a single value
+/// and combining like dice (same sides and suffixes) added or subtracted in the same direction,
+/// and the normalized forms are compared.
+///
+/// Equivalence is structural after normalization rather than distributional, so `2d6-1d6` is
+/// not considered equivalent to `1d6`. For expressions that only add standard dice and
+/// modifiers, the two notions coincide.
+pub fn are_equivalent(a: &str, b: &str) -> Result<bool, RollError> {
+    Ok(normalize(&parse_terms(a)?) == normalize(&parse_terms(b)?))
+}
+
+/// Identifies dice that can be combined when normalizing an expression: sides, suffixes and
+/// whether the dice are subtracted. Dice that keep only some of their number cannot be combined
+/// with other dice (`2d20kh1+2d20kh1` is not `4d20kh2`), so such terms are identified by their
+/// count as well, and the normalized expression counts the terms rather than the dice. Fudge dice
+/// have no sides, and custom dice are identified by their faces compared as a multiset, so the
+/// order they are written in does not matter (`2d[1,2]` is `2d[2,1]`) but repeats do.
+type DiceKind = (
+    Option<u16>,
+    Vec<i32>,
+    Option<u16>,
+    (bool, bool, bool),
+    Option<Comparison>,
+    (Option<u16>, Option<u16>),
+    Option<(i16, Keep)>,
+    Option<Comparison>,
+    bool,
+);
+
+/// Normalizes parsed terms into the total number of dice of each kind and the net modifier.
+fn normalize(terms: &[DieRollTerm]) -> (BTreeMap<DiceKind, i32>, i64) {
+    let mut dice = BTreeMap::new();
+    let mut modifier = 0i64;
+
+    for t in terms {
+        match *t {
+            DieRollTerm::Modifier(n) => modifier += i64::from(n),
+            DieRollTerm::DieRoll { multiplier: 0, .. }
+            | DieRollTerm::Fudge { multiplier: 0 }
+            | DieRollTerm::Custom { multiplier: 0, .. } => {}
+            DieRollTerm::Custom { multiplier: m, ref faces } => {
+                let mut faces = faces.clone();
+                faces.sort();
+                let kind = (None, faces, None, (false, false, false), None, (None, None), None, None, m < 0);
+                *dice.entry(kind).or_insert(0) += i32::from(m);
+            }
+            DieRollTerm::Fudge { multiplier: m } => {
+                let kind = (None, Vec::new(), None, (false, false, false), None, (None, None), None, None, m < 0);
+                *dice.entry(kind).or_insert(0) += i32::from(m);
+            }
+            DieRollTerm::DieRoll {
+                multiplier: m,
+                sides,
+                open_ended,
+                exploding,
+                compounding,
+                penetrating,
+                reroll,
+                min,
+                max,
+                keep,
+                success,
+            } => {
+                let explosion = (exploding, compounding, penetrating);
+                let clamp = (min, max);
+                let keep = keep.map(|k| (m, k));
+                let kind = (Some(sides), Vec::new(), open_ended, explosion, reroll, clamp, keep, success, m < 0);
+                *dice.entry(kind).or_insert(0) += if keep.is_some() { 1 } else { i32::from(m) };
+            }
+        }
+    }
+    (dice, modifier)
+}
+
+/// Computes the expected (mean) total of a die roll expression without rolling any dice. A fair
+/// n-sided die averages `(n + 1) / 2`, so `2d6+1` averages 8, and modifiers contribute their
+/// value. Rerolled and kept dice, dice pools and exploding dice are all accounted for exactly,
+/// along with any minimum or maximum on their faces; open-ended dice and division are an error.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     assert_eq!(d20::mean("4d6 + 2").unwrap(), 16.0);
+///     assert_eq!(d20::mean("2d10 + 4").unwrap(), 15.0);
+/// # }
+/// ```
+pub fn mean(s: &str) -> Result<f64, RollError> {
+    let (_, terms, expr) = parse_input(s)?;
+
+    let terms = terms.iter().map(DieRollTerm::mean).collect::<Result<Vec<_>, _>>()?;
+    expr.mean(&terms)
+}
+
+/// Returns the lowest total a die roll expression can produce, without rolling any dice. Each die
+/// contributes its lowest face (or, for a dice pool, the fewest successes it can score), and
+/// subtracted dice contribute their highest. Exploding and open-ended dice are an error.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     assert_eq!(d20::min_total("2d6 + 3").unwrap(), 5);
+///     assert_eq!(d20::min_total("1d20 - 1d4").unwrap(), -3);
+/// # }
+/// ```
+pub fn min_total(s: &str) -> Result<i64, RollError> {
+    bounds(s).map(|(low, _)| low)
+}
+
+/// Returns the highest total a die roll expression can produce, without rolling any dice. See
+/// `min_total()`.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     assert_eq!(d20::max_total("2d6 + 3").unwrap(), 15);
+///     assert_eq!(d20::max_total("1d20 - 1d4").unwrap(), 19);
+/// # }
+/// ```
+pub fn max_total(s: &str) -> Result<i64, RollError> {
+    bounds(s).map(|(_, high)| high)
+}
+
+fn bounds(s: &str) -> Result<(i64, i64), RollError> {
+    let (_, terms, expr) = parse_input(s)?;
+
+    let terms = terms.iter().map(DieRollTerm::bounds).collect::<Result<Vec<_>, _>>()?;
+    expr.bounds(&terms)
+}
+
+/// Computes the exact variance of the total of a die roll expression without rolling any dice.
+/// Terms are independent, so the variance of the expression is the sum of the variances of its
+/// terms: each n-sided die contributes `(n² - 1) / 12` and modifiers contribute nothing.
+/// Subtracted dice (e.g. `-2d6`) spread the total exactly as much as added dice do.
+///
+/// For example, the variance of `2d6` is `2 × 35/12`.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     assert!((d20::variance("1d6").unwrap() - 2.9167).abs() < 1e-4);
+///     assert!(d20::variance("1d20").unwrap() > d20::variance("3d6 + 1").unwrap());
+/// # }
+/// ```
+pub fn variance(s: &str) -> Result<f64, RollError> {
+    let terms = parse_terms(s)?;
+
+    terms.iter().map(|t| t.variance()).sum()
+}
+
+/// Computes the exact standard deviation of the total of a die roll expression, i.e. the square
+/// root of its `variance()`.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     assert!((d20::std_dev("1d20").unwrap() - 5.766).abs() < 1e-3);
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn std_dev(s: &str) -> Result<f64, RollError> {
+    variance(s).map(|v| v.sqrt())
+}
+
+/// Computes an exact statistical summary of a die roll expression in one call: the mean,
+/// variance, lowest and highest possible totals, and the most likely total. Everything is
+/// derived from the exact probability distribution of the expression rather than sampled.
+///
+/// Open-ended dice have no upper bound and cannot be summarized exactly, and expressions with
+/// too many possible outcomes to enumerate are rejected.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let summary = d20::exact_summary("2d6 + 1").unwrap();
+///     assert_eq!(summary.min, 3);
+///     assert_eq!(summary.max, 13);
+///     assert_eq!(summary.mode, 8);
+///     assert!((summary.mean - 8.0).abs() < 1e-9);
+/// # }
+/// ```
+pub fn exact_summary(s: &str) -> Result<ExactSummary, RollError> {
+    let terms = parse_terms(s)?;
+    let dist = exact_distribution(&terms)?;
+
+    let mean = dist.iter().map(|(&t, &p)| t as f64 * p).sum();
+    let variance = terms.iter().map(|t| t.variance()).sum::<Result<f64, _>>()?;
+    let (&min, _) = dist.iter().next().unwrap();
+    let (&max, _) = dist.iter().next_back().unwrap();
+    let peak = dist.values().cloned().fold(0.0, f64::max);
+    // totals whose probabilities differ only by rounding error are equally likely
+    let (&mode, _) = dist.iter().find(|&(_, &p)| p >= peak * (1.0 - 1e-9)).unwrap();
+
+    Ok(ExactSummary { mean, variance, min, max, mode })
+}
+
+/// An exact statistical summary of a die roll expression, computed by `exact_summary()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExactSummary {
+    /// The expected (mean) total
+    pub mean: f64,
+    /// The variance of the total
+    pub variance: f64,
+    /// The lowest possible total
+    pub min: i64,
+    /// The highest possible total
+    pub max: i64,
+    /// The most likely total, or the lowest of them if several are equally likely
+    pub mode: i64,
+}
+
+/// Computes the chance that a die roll expression totals at least `target`, as when checking
+/// whether `1d20+7` hits an armor class of 15. The chance is exact, found from the probability
+/// distribution of the expression, unless its dice explode or it has too many outcomes to
+/// enumerate; it is then estimated from `PROBABILITY_SAMPLES` rolls, which needs the `std`
+/// feature. Like `variance()`, only expressions that add and subtract can be analyzed.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     assert_eq!(d20::probability_at_least("1d20 + 7", 15).unwrap(), 0.65);
+///     assert_eq!(d20::probability_at_least("1d6", 7).unwrap(), 0.0);
+/// # }
+/// ```
+pub fn probability_at_least(s: &str, target: i32) -> Result<f64, RollError> {
+    let terms = parse_terms(s)?;
+    match exact_distribution(&terms) {
+        Ok(dist) => {
+            // weighing the totals that hit against those that miss cancels out the rounding
+            // error of adding up their probabilities, so a coin flip comes out at exactly 0.5
+            let hits: f64 = dist.range(i64::from(target)..).map(|(_, &p)| p).sum();
+            let misses: f64 = dist.range(..i64::from(target)).map(|(_, &p)| p).sum();
+            Ok(hits / (hits + misses))
+        }
+        Err(e @ RollError::Unsupported(_)) | Err(e @ RollError::TooLarge) => {
+            sampled_probability_at_least(s, target, e)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Computes the exact probability of every possible total of a die roll expression, by
+/// convolving the distributions of its terms rather than rolling any dice. The probabilities
+/// add up to 1, and totals that cannot come up are left out of the map.
+///
+/// Only expressions that add and subtract can be analyzed, and exploding dice have no exact
+/// distribution. An expression with too many outcomes to enumerate, such as `40d100kh3`, fails
+/// with `RollError::TooLarge`.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let p = d20::probabilities("2d6").unwrap();
+///     assert_eq!(p.len(), 11);
+///     assert!((p[&7] - 6.0 / 36.0).abs() < 1e-9);
+///     assert!((p.values().sum::<f64>() - 1.0).abs() < 1e-9);
+/// # }
+/// ```
+pub fn probabilities(s: &str) -> Result<BTreeMap<i64, f64>, RollError> {
+    let mut dist = exact_distribution(&parse_terms(s)?)?;
+    let mass: f64 = dist.values().sum();
+    for p in dist.values_mut() {
+        *p /= mass;
+    }
+    Ok(dist)
+}
+
+/// The number of rolls `probability_at_least()` makes to estimate the chance of an expression
+/// it cannot compute exactly. The estimate is usually within half a percentage point.
+pub const PROBABILITY_SAMPLES: usize = 100_000;
+
+/// Estimates the chance that a die roll expression totals at least `target` by rolling it
+/// `PROBABILITY_SAMPLES` times.
+#[cfg(feature = "std")]
+fn sampled_probability_at_least(s: &str, target: i32, _: RollError) -> Result<f64, RollError> {
+    let mut hits = 0;
+    for total in totals(s, PROBABILITY_SAMPLES)? {
+        if total? >= i64::from(target) {
+            hits += 1;
+        }
+    }
+    Ok(f64::from(hits) / PROBABILITY_SAMPLES as f64)
+}
+
+/// Without `std` there is no generator to sample with, so an expression that cannot be computed
+/// exactly is unsupported.
+#[cfg(not(feature = "std"))]
+fn sampled_probability_at_least(_: &str, _: i32, e: RollError) -> Result<f64, RollError> {
+    Err(e)
+}
+
+/// The most work (in multiplications) the exact distribution of an expression may take to
+/// compute before the expression is considered too large.
+const MAX_DISTRIBUTION_WORK: usize = 50_000_000;
+
+/// Computes the exact probability of every possible total of an expression by convolving the
+/// distributions of its terms.
+fn exact_distribution(terms: &[DieRollTerm]) -> Result<BTreeMap<i64, f64>, RollError> {
+    let mut work = 0usize;
+    let mut convolve = |a: (i64, Vec<f64>), b: &(i64, Vec<f64>)| {
+        work += a.1.len() * b.1.len();
+        if work > MAX_DISTRIBUTION_WORK {
+            return Err(RollError::TooLarge);
+        }
+        let mut p = vec![0.0; a.1.len() + b.1.len() - 1];
+        for (i, x) in a.1.iter().enumerate() {
+            for (j, y) in b.1.iter().enumerate() {
+                p[i + j] += x * y;
+            }
+        }
+        Ok((a.0 + b.0, p))
+    };
+
+    let mut dist = (0i64, vec![1.0]);
+    for t in terms {
+        let (m, single) = match *t {
+            DieRollTerm::Modifier(n) => {
+                dist.0 += i64::from(n);
+                continue;
+            }
+            DieRollTerm::DieRoll { .. } if t.explodes() => {
+                return Err(RollError::Unsupported("exploding dice have no exact distribution"));
+            }
+            DieRollTerm::DieRoll { keep: Some(_), .. } => {
+                dist = convolve(dist, &t.keep_distribution()?)?;
+                continue;
+            }
+            DieRollTerm::DieRoll { multiplier: m, success: Some(c), .. } => {
+                let p = c.probability(&t.face_distribution());
+                (m, (0, vec![1.0 - p, p]))
+            }
+            DieRollTerm::DieRoll { multiplier: m, .. } => (m, (1, t.face_distribution())),
+            DieRollTerm::Fudge { multiplier: m } => (m, (-1, vec![1.0 / 3.0; 3])),
+            DieRollTerm::Custom { multiplier: m, ref faces } => {
+                let low = faces.iter().cloned().min().unwrap_or(0);
+                let high = faces.iter().cloned().max().unwrap_or(0);
+                if i64::from(high) - i64::from(low) >= MAX_DISTRIBUTION_WORK as i64 {
+                    return Err(RollError::TooLarge);
+                }
+                let mut p = vec![0.0; (high - low) as usize + 1];
+                for &f in faces {
+                    p[(f - low) as usize] += 1.0 / faces.len() as f64;
+                }
+                (m, (i64::from(low), p))
+            }
+        };
+
+        let mut term = (0i64, vec![1.0]);
+        for _ in 0..m.unsigned_abs() {
+            term = convolve(term, &single)?;
+        }
+        if m < 0 {
+            term.1.reverse();
+            term.0 = -(term.0 + term.1.len() as i64 - 1);
+        }
+        dist = convolve(dist, &term)?;
+    }
+
+    let (offset, p) = dist;
+    Ok(p.into_iter()
+        .enumerate()
+        .filter(|&(_, p)| p > 0.0)
+        .map(|(i, p)| (offset + i as i64, p))
+        .collect())
+}
+
+/// Raises `x` to the power `k` by repeated multiplication, as `f64::powi()` needs `std`.
+fn powi(x: f64, k: i32) -> f64 {
+    let base = if k < 0 { 1.0 / x } else { x };
+    (0..k.unsigned_abs()).fold(1.0, |product, _| product * base)
+}
+
+/// Returns the average of the faces of a custom die.
+fn custom_mean(faces: &[i32]) -> f64 {
+    faces.iter().map(|&f| f64::from(f)).sum::<f64>() / faces.len() as f64
+}
+
+/// Renders a term as it appears in an expression: a `signed` term, one that is added to what
+/// comes before it, is always preceded by its sign, while any other term never carries a
+/// redundant `+`.
+fn signed_term(signed: bool, t: &DieRollTerm) -> String {
+    let term = format!("{}", t);
+    if !signed {
+        term.trim_start_matches('+').to_string()
+    } else if term.starts_with('-') || term.starts_with('+') {
+        term
+    } else {
+        format!("+{}", term)
+    }
+}
+
+/// How the results of the terms of an expression combine into its total. Terms carry their own
+/// sign, so a sum simply adds up its parts, while `*` and `/` bind more tightly than the signs:
+/// `2d6+1d4*3` adds `1d4*3` to `2d6`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Expr {
+    /// The result of the term at this index of the expression
+    Term(usize),
+    /// The sum of the results of the parts
+    Sum(Vec<Expr>),
+    /// The left result multiplied or divided by the right
+    Product(Box<Expr>, Operator, Box<Expr>),
+    /// The result of a parenthesized group, subtracted rather than added
+    Negate(Box<Expr>),
+}
+
+/// An operator combining the results of two parts of an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Operator {
+    Multiply,
+    Divide,
+}
+
+impl Expr {
+    /// Computes the total of the expression from the result of each of its terms. Division
+    /// rounds toward zero.
+    fn evaluate(&self, subtotals: &[i64]) -> Result<i64, RollError> {
+        match *self {
+            Expr::Term(i) => Ok(subtotals[i]),
+            Expr::Sum(ref parts) => parts.iter().try_fold(0i64, |sum, p| {
+                sum.checked_add(p.evaluate(subtotals)?).ok_or(RollError::Overflow)
+            }),
+            Expr::Product(ref left, op, ref right) => {
+                let (left, right) = (left.evaluate(subtotals)?, right.evaluate(subtotals)?);
+                match op {
+                    Operator::Multiply => left.checked_mul(right).ok_or(RollError::Overflow),
+                    Operator::Divide if right == 0 => Err(RollError::DivisionByZero),
+                    Operator::Divide => left.checked_div(right).ok_or(RollError::Overflow),
+                }
+            }
+            Expr::Negate(ref group) => group.evaluate(subtotals)?.checked_neg().ok_or(RollError::Overflow),
+        }
+    }
+
+    /// Computes the expected total of the expression from the expected result of each of its
+    /// terms. Terms are independent, so the mean of a product is the product of the means, but
+    /// there is no such shortcut for division.
+    fn mean(&self, terms: &[f64]) -> Result<f64, RollError> {
+        match *self {
+            Expr::Term(i) => Ok(terms[i]),
+            Expr::Sum(ref parts) => parts.iter().map(|p| p.mean(terms)).sum(),
+            Expr::Product(ref left, Operator::Multiply, ref right) => {
+                Ok(left.mean(terms)? * right.mean(terms)?)
+            }
+            Expr::Product(_, Operator::Divide, _) => {
+                Err(RollError::Unsupported("division has no exact mean"))
+            }
+            Expr::Negate(ref group) => group.mean(terms).map(|m| -m),
+        }
+    }
+
+    /// Computes the lowest and highest possible totals of the expression from the bounds of each
+    /// of its terms.
+    fn bounds(&self, terms: &[(i64, i64)]) -> Result<(i64, i64), RollError> {
+        match *self {
+            Expr::Term(i) => Ok(terms[i]),
+            Expr::Sum(ref parts) => parts.iter().try_fold((0i64, 0i64), |(low, high), p| {
+                let (l, h) = p.bounds(terms)?;
+                match (low.checked_add(l), high.checked_add(h)) {
+                    (Some(low), Some(high)) => Ok((low, high)),
+                    _ => Err(RollError::Overflow),
+                }
+            }),
+            Expr::Product(ref left, op, ref right) => {
+                let (a, b) = (left.bounds(terms)?, right.bounds(terms)?);
+                // the extremes lie at the ends of the operands, or for a divisor that spans zero,
+                // at the divisors closest to zero
+                let mut divisors = vec![b.0, b.1];
+                if op == Operator::Divide {
+                    divisors.extend([-1, 1].iter().filter(|&&d| b.0 <= d && d <= b.1));
+                    divisors.retain(|&d| d != 0);
+                }
+                let mut results = Vec::new();
+                for &x in &[a.0, a.1] {
+                    for &y in &divisors {
+                        results.push(match op {
+                            Operator::Multiply => x.checked_mul(y),
+                            Operator::Divide => x.checked_div(y),
+                        });
+                    }
+                }
+                let results = results.into_iter().collect::<Option<Vec<i64>>>().ok_or(RollError::Overflow)?;
+                match (results.iter().min(), results.iter().max()) {
+                    (Some(&low), Some(&high)) => Ok((low, high)),
+                    _ => Err(RollError::DivisionByZero),
+                }
+            }
+            Expr::Negate(ref group) => {
+                let (low, high) = group.bounds(terms)?;
+                match (high.checked_neg(), low.checked_neg()) {
+                    (Some(low), Some(high)) => Ok((low, high)),
+                    _ => Err(RollError::Overflow),
+                }
+            }
+        }
+    }
+
+    /// Returns whether the expression only adds up its terms, without multiplying, dividing or
+    /// subtracting a group.
+    fn is_additive(&self) -> bool {
+        match *self {
+            Expr::Term(_) => true,
+            Expr::Sum(ref parts) => parts.iter().all(Expr::is_additive),
+            Expr::Product(..) | Expr::Negate(_) => false,
+        }
+    }
+
+    /// Returns a copy of the expression with every term replaced by the result of `f`, which is
+    /// called on the terms in order.
+    fn map_terms<F: FnMut(usize) -> Expr>(&self, f: &mut F) -> Expr {
+        match *self {
+            Expr::Term(i) => f(i),
+            Expr::Sum(ref parts) => Expr::Sum(parts.iter().map(|p| p.map_terms(f)).collect()),
+            Expr::Product(ref left, op, ref right) => {
+                Expr::Product(Box::new(left.map_terms(f)), op, Box::new(right.map_terms(f)))
+            }
+            Expr::Negate(ref group) => Expr::Negate(Box::new(group.map_terms(f))),
+        }
+    }
+
+    /// Renders the expression, rendering each term with `term`, which is given the index of the
+    /// term and whether it must be preceded by its sign.
+    fn format(&self, term: &dyn Fn(usize, bool) -> String) -> String {
+        let mut out = String::new();
+        self.format_into(term, false, &mut out);
+        out
+    }
+
+    fn format_into(&self, term: &dyn Fn(usize, bool) -> String, signed: bool, out: &mut String) {
+        match *self {
+            Expr::Term(i) => out.push_str(&term(i, signed)),
+            Expr::Sum(ref parts) => {
+                for (i, p) in parts.iter().enumerate() {
+                    p.format_into(term, signed || i > 0, out);
+                }
+            }
+            Expr::Product(ref left, op, ref right) => {
+                left.format_operand(term, signed, false, out);
+                out.push(if op == Operator::Multiply { '*' } else { '/' });
+                right.format_operand(term, false, true, out);
+            }
+            Expr::Negate(ref group) => {
+                out.push_str("-(");
+                group.format_into(term, false, out);
+                out.push(')');
+            }
+        }
+    }
+
+    /// Renders an operand of `*` or `/`, in parentheses if it is a sum of several parts or, on
+    /// the `right` of the operator, another product.
+    fn format_operand(&self, term: &dyn Fn(usize, bool) -> String, signed: bool, right: bool,
+                      out: &mut String) {
+        let mut inner = self;
+        while let Expr::Sum(ref parts) = *inner {
+            match parts.len() {
+                1 => inner = &parts[0],
+                _ => break,
+            }
+        }
+        match *inner {
+            Expr::Sum(_) => {}
+            Expr::Product(..) if right => {}
+            _ => return inner.format_into(term, signed, out),
+        }
+        out.push_str(if signed { "+(" } else { "(" });
+        inner.format_into(term, false, out);
+        out.push(')');
     }
 }
 
-fn parse_die_roll_terms(drex: &str) -> Vec<DieRollTerm> {
+#[cfg(all(test, feature = "std"))]
+fn parse_die_roll_terms(drex: &str) -> Result<Vec<DieRollTerm>, RollError> {
+    parser::parse_tree(drex).map(|(terms, _)| terms)
+}
+
+/// Parses a die roll expression without rolling it, returning its terms in the order they
+/// appear. The expression is checked just as `roll_dice()` checks it, so this can validate an
+/// expression, or describe it, before it is rolled. The numbers that `*` and `/` apply are
+/// terms too, so `1d6*10` has the terms `1d6` and `+10`.
+///
+/// ```rust
+/// # extern crate d20;
+/// # use d20::DieRollTerm;
+/// # fn main() {
+///     let terms = d20::parse_expression("2d6 + 5").unwrap();
+///     assert_eq!(terms, vec!["2d6".parse::<DieRollTerm>().unwrap(), DieRollTerm::Modifier(5)]);
+///     assert!(d20::parse_expression("2d6 + 1d0").is_err());
+/// # }
+/// ```
+pub fn parse_expression(s: &str) -> Result<Vec<DieRollTerm>, RollError> {
+    parse_input(s).map(|(_, terms, _)| terms)
+}
+
+/// Returns whether `s` is a valid die roll expression, i.e. whether `roll_dice()` would roll it,
+/// without rolling any dice. Empty and whitespace-only strings are not valid.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     assert!(d20::is_valid("2d6 + 5"));
+///     assert!(!d20::is_valid("roll four chickens"));
+/// # }
+/// ```
+pub fn is_valid(s: &str) -> bool {
+    parse_expression(s).is_ok()
+}
+
+/// Parses as much of a die roll expression as possible, for previewing expressions that are
+/// still being typed. Unlike `roll_dice()`, which accepts or rejects the whole expression, this
+/// returns the terms parsed from the start of the expression along with where and why parsing
+/// stopped, if it did not reach the end.
+///
+/// Positions are byte offsets into `s` as given, including any whitespace.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let partial = d20::parse_partial("2d6 + ");
+///     assert_eq!(partial.terms.len(), 1);
+///
+///     let stop = partial.stopped.unwrap();
+///     assert_eq!(stop.position, 4);
+///     assert_eq!(stop.reason, "expecting a number");
+/// # }
+/// ```
+pub fn parse_partial(s: &str) -> PartialParse {
+    // parse the whitespace-free expression, as `roll_dice` does, but keep track of where each
+    // character came from so positions refer to the input as typed
+    let (drex, offsets) = strip_whitespace(s);
+    let position = |i: usize| offsets.get(i).cloned().unwrap_or(s.len());
+
     let mut terms = Vec::new();
+    let mut i = 0;
+    // whether the next token must start an operand (a term or a group), as at the start of the
+    // expression, after `*` or `/` and after an opening parenthesis
+    let mut operand = true;
+    let mut depth = 0;
+
+    while i < drex.len() {
+        let rest = &drex[i..];
+        let open = if rest.starts_with("+(") || rest.starts_with("-(") {
+            2
+        } else if operand && rest.starts_with('(') {
+            1
+        } else {
+            0
+        };
+        if open > 0 {
+            depth += 1;
+            i += open;
+            operand = true;
+            continue;
+        }
+        if !operand && rest.starts_with(['*', '/']) {
+            operand = true;
+            i += 1;
+            continue;
+        }
+        if !operand && depth > 0 && rest.starts_with(')') {
+            depth -= 1;
+            i += 1;
+            continue;
+        }
+        operand = false;
+        let end = match term_len(rest) {
+            Some(end) => end,
+            None => return PartialParse::stopped(terms, position(i), stop_reason(rest)),
+        };
+        if rest[end..].starts_with(['d', 'D']) {
+            return PartialParse::stopped(terms, position(i), "expecting the number of sides");
+        }
+
+        let term = match DieRollTerm::try_parse(&rest[..end]) {
+            Ok(term) => term,
+            Err(_) => return PartialParse::stopped(terms, position(i), "number too large"),
+        };
+        if let Err(reason) = term.validate() {
+            return PartialParse::stopped(terms, position(i), reason);
+        }
+        terms.push(term);
+        i += end;
+    }
+
+    if terms.is_empty() {
+        PartialParse::stopped(terms, s.len(), "expecting a die roll term")
+    } else if operand {
+        PartialParse::stopped(terms, s.len(), "expecting a number")
+    } else if depth > 0 {
+        PartialParse::stopped(terms, s.len(), "expecting ')'")
+    } else {
+        PartialParse { terms, stopped: None }
+    }
+}
 
-    let re = Regex::new(r"([+-]?\s*\d+[dD]\d+|[+-]?\s*\d+)").unwrap();
+fn stop_reason(rest: &str) -> &'static str {
+    let mut chars = rest.chars();
+    match chars.next() {
+        Some('+') | Some('-') if chars.next().is_some_and(|c| c == 'd' || c == 'D') => {
+            "expecting the number of sides"
+        }
+        Some('+') | Some('-') => "expecting a number",
+        Some('d') | Some('D') => "expecting the number of sides",
+        Some('<') | Some('>') | Some('=') => "expecting a target number",
+        _ => "unexpected character",
+    }
+}
+
+/// The result of parsing as much of a die roll expression as possible with `parse_partial()`.
+#[derive(Debug)]
+pub struct PartialParse {
+    /// The terms successfully parsed from the start of the expression
+    pub terms: Vec<DieRollTerm>,
+    /// Where and why parsing stopped, or `None` if the whole expression was parsed
+    pub stopped: Option<ParseStop>,
+}
 
-    let matches = re.find_iter(drex);
-    for m in matches {
-        terms.push(DieRollTerm::parse(&drex[m.start()..m.end()]));
+impl PartialParse {
+    fn stopped(terms: Vec<DieRollTerm>, position: usize, reason: &'static str) -> PartialParse {
+        PartialParse { terms, stopped: Some(ParseStop { position, reason }) }
     }
-    terms
+}
+
+/// Describes where `parse_partial()` stopped parsing an expression, and why.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseStop {
+    /// The byte offset into the expression at which parsing stopped
+    pub position: usize,
+    /// A short description of what was expected at that position
+    pub reason: &'static str,
 }
 
 /// Generates a random number within the specified range. Returns a `Result` containing
 /// either a valid signed 32-bit integer with the randomly generated number or some text 
 /// indicating the reason for failure.
-pub fn roll_range<'a>(min: i32, max: i32) -> Result<i32, &'a str> {
+///
+/// Both bounds are inclusive, so `roll_range(1, 6)` can return 1 and 6 alike. Any `i32` bounds
+/// are accepted, up to and including `i32::MAX`.
+#[cfg(feature = "std")]
+pub fn roll_range(min: i32, max: i32) -> Result<i32, RollError> {
     if min > max {
-        Err("Invalid range: min must be less than or equal to max")
+        Err(RollError::InvalidRange { min, max })
     } else {
-        Ok(thread_rng().gen_range(min, max + 1))
+        // widen the exclusive upper bound so that a `max` of `i32::MAX` cannot overflow
+        Ok(thread_rng().gen_range(i64::from(min), i64::from(max) + 1) as i32)
+    }
+}
+
+/// Generates a random number within an inclusive range, like `roll_range()`.
+///
+/// ```rust
+/// # extern crate d20;
+/// # fn main() {
+///     let percent = d20::roll_range_inclusive(1..=100).unwrap();
+///     assert!(percent >= 1 && percent <= 100);
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn roll_range_inclusive(range: RangeInclusive<i32>) -> Result<i32, RollError> {
+    roll_range(*range.start(), *range.end())
+}
+
+/// A press-your-luck roller: each `push()` rolls another die and adds it to a running total,
+/// until the player either stops or _busts_, losing everything accumulated so far.
+///
+/// ```rust
+/// # extern crate d20;
+/// # use d20::{Bust, PushYourLuck};
+/// # fn main() {
+///     let mut run = PushYourLuck::new(6, Bust::On(1)).unwrap();
+///     while !run.is_bust() && run.total() < 10 {
+///         run.push();
+///     }
+///     assert!(run.is_bust() || run.total() >= 10);
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct PushYourLuck {
+    die: DieRollTerm,
+    bust: Bust,
+    dice: Vec<i32>,
+    busted: bool,
+}
+
+/// The condition under which a `PushYourLuck` run busts.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bust {
+    /// The run busts when a die shows the given face
+    On(i32),
+    /// The run busts when a die matches a face already rolled during the run
+    Duplicate,
+}
+
+#[cfg(feature = "std")]
+impl PushYourLuck {
+    /// Starts a new run rolling `sides`-sided dice, busting on the given condition.
+    pub fn new(sides: u16, bust: Bust) -> Result<PushYourLuck, RollError> {
+        let die = DieRollTerm::DieRoll {
+            multiplier: 1,
+            sides,
+            open_ended: None,
+            exploding: false,
+            compounding: false,
+            penetrating: false,
+            reroll: None,
+            min: None,
+            max: None,
+            keep: None,
+            success: None,
+        };
+        die.check()?;
+
+        Ok(PushYourLuck { die, bust, dice: Vec::new(), busted: false })
+    }
+
+    /// Rolls another die and adds it to the run, returning whether the run has busted. Once a
+    /// run has busted, pushing again rolls nothing and keeps reporting the bust.
+    pub fn push(&mut self) -> bool {
+        if self.busted {
+            return true;
+        }
+        let mut faces = Vec::with_capacity(1);
+        self.die.roll_into(&mut faces, &mut thread_rng());
+        let face = faces[0];
+
+        self.busted = match self.bust {
+            Bust::On(n) => face == n,
+            Bust::Duplicate => self.dice.contains(&face),
+        };
+        self.dice.push(face);
+        self.busted
+    }
+
+    /// The running total of the dice rolled so far, or 0 if the run has busted.
+    pub fn total(&self) -> i32 {
+        if self.busted {
+            0
+        } else {
+            self.dice.iter().sum()
+        }
+    }
+
+    /// Whether the run has busted.
+    pub fn is_bust(&self) -> bool {
+        self.busted
+    }
+
+    /// Every die rolled during the run, including the one that busted it.
+    pub fn dice(&self) -> &[i32] {
+        &self.dice
+    }
+}
+
+/// A random table mapping the results of a d100 roll to outcomes, as found in most adventure
+/// modules.
+///
+/// ```rust
+/// # extern crate d20;
+/// # use d20::RollTable;
+/// # fn main() {
+///     let table = RollTable::new(vec![(1..=60, "Goblins"), (61..=95, "Wolves"), (96..=100, "Dragon")])
+///         .unwrap();
+///     let (roll, outcome) = table.roll_on().unwrap();
+///     assert!(roll >= 1 && roll <= 100);
+///     assert!(["Goblins", "Wolves", "Dragon"].contains(&outcome));
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct RollTable {
+    entries: Vec<(RangeInclusive<i32>, String)>,
+}
+
+#[cfg(feature = "std")]
+impl RollTable {
+    /// Creates a table from its entries, which may be given in any order. The ranges must not
+    /// overlap and must together cover every result from 1 to 100.
+    pub fn new(entries: Vec<(RangeInclusive<i32>, &str)>) -> Result<RollTable, RollError> {
+        let mut entries: Vec<(RangeInclusive<i32>, String)> = entries
+            .into_iter()
+            .map(|(r, outcome)| (r, outcome.to_string()))
+            .collect();
+        entries.sort_by_key(|e| *e.0.start());
+
+        let mut next = 1;
+        for (r, _) in &entries {
+            if r.start() > r.end() {
+                return Err(RollError::InvalidArgument("roll table has an empty range"));
+            } else if *r.end() > 100 {
+                return Err(RollError::InvalidArgument("roll table ranges must not go past 100"));
+            } else if *r.start() < next {
+                return Err(RollError::InvalidArgument("roll table ranges overlap"));
+            } else if *r.start() > next {
+                return Err(RollError::InvalidArgument("roll table ranges leave a gap"));
+            }
+            next = r.end() + 1;
+        }
+        if next != 101 {
+            return Err(RollError::InvalidArgument("roll table ranges must cover 1 to 100"));
+        }
+
+        Ok(RollTable { entries })
+    }
+
+    /// Rolls a d100 and returns the result along with the outcome it selects on the table.
+    pub fn roll_on(&self) -> Result<(i32, &str), RollError> {
+        let roll = roll_range(1, 100)?;
+        self.entries
+            .iter()
+            .find(|e| e.0.contains(&roll))
+            .map(|e| (roll, e.1.as_str()))
+            .ok_or(RollError::InvalidArgument("roll table has no entry for the result"))
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests;
@@ -0,0 +1,103 @@
+//! The `d20` command rolls a die roll expression given on the command line, or read from
+//! standard input when none is given, and prints the roll.
+//!
+//! ```text
+//! $ d20 "3d6 + 4"
+//! 3d6[2, 5, 1]+4 (Total: 12)
+//! $ d20 --count 3 1d20
+//! ```
+//!
+//! `--count N` rolls the expression `N` times, one roll to a line, and `--seed S` rolls with a
+//! generator seeded with `S`, so the same seed always prints the same rolls. A bad expression
+//! is reported on standard error with an exit code of 1, and bad arguments with an exit code
+//! of 2.
+
+extern crate d20;
+extern crate rand;
+
+use std::env;
+use std::io::{self, Read};
+use std::process;
+
+use d20::RollExpr;
+use rand::{Isaac64Rng, SeedableRng};
+
+const USAGE: &str = "usage: d20 [--count N] [--seed S] [EXPRESSION]";
+
+struct Args {
+    expression: Option<String>,
+    count: usize,
+    seed: Option<u64>,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+    let mut parsed = Args { expression: None, count: 1, seed: None };
+    let mut words = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Err(String::new()),
+            "-n" | "--count" => {
+                let n = args.next().ok_or("--count needs a number of rolls")?;
+                parsed.count = n.parse().map_err(|_| format!("invalid count '{}'", n))?;
+            }
+            "--seed" => {
+                let s = args.next().ok_or("--seed needs a number")?;
+                parsed.seed = Some(s.parse().map_err(|_| format!("invalid seed '{}'", s))?);
+            }
+            // a leading minus is part of an expression such as `-2` rather than a flag
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+            _ => words.push(arg),
+        }
+    }
+    if !words.is_empty() {
+        parsed.expression = Some(words.join(" "));
+    }
+    Ok(parsed)
+}
+
+fn run(args: Args) -> Result<(), d20::RollError> {
+    let expression = match args.expression {
+        Some(e) => e,
+        None => {
+            let mut input = String::new();
+            if let Err(e) = io::stdin().read_to_string(&mut input) {
+                eprintln!("d20: could not read standard input: {}", e);
+                process::exit(2);
+            }
+            input.trim().to_string()
+        }
+    };
+    let expr = RollExpr::parse(&expression)?;
+    match args.seed {
+        Some(seed) => {
+            let mut rng = Isaac64Rng::from_seed(&[seed][..]);
+            for _ in 0..args.count {
+                println!("{}", expr.roll_with(&mut rng)?);
+            }
+        }
+        None => {
+            for r in expr.into_iter().take(args.count) {
+                println!("{}", r?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(ref e) if e.is_empty() => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("d20: {}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = run(args) {
+        eprintln!("d20: {}", e);
+        process::exit(1);
+    }
+}
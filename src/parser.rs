@@ -0,0 +1,340 @@
+//! Parsing of die roll expressions. Expressions are split into tokens by a hand-written
+//! scanner, or by a regular expression when the `regex` feature is enabled, and the tokens are
+//! parsed by recursive descent into the terms of the expression and how they combine.
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::string::{String, ToString};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(feature = "regex")]
+use std::sync::OnceLock;
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use {DieRollTerm, Expr, Operator, RollError};
+
+/// Parses a die roll expression as entered, returning it with the whitespace removed along with
+/// its terms and how they combine. Parse failures are positioned in `s`.
+pub(crate) fn parse_input(s: &str) -> Result<(String, Vec<DieRollTerm>, Expr), RollError> {
+    let (drex, offsets) = strip_whitespace(s);
+    match parse_tree(&drex) {
+        Ok((terms, expr)) => Ok((drex, terms, expr)),
+        Err(RollError::ParseFailure { position, fragment }) => Err(RollError::ParseFailure {
+            position: offsets.get(position).cloned().unwrap_or(s.len()),
+            fragment,
+        }),
+        Err(e) => Err(e),
+    }
+}
+
+/// Removes the whitespace from a die roll expression, returning it along with the byte offset
+/// in `s` of each byte of the result.
+pub(crate) fn strip_whitespace(s: &str) -> (String, Vec<usize>) {
+    let mut drex = String::with_capacity(s.len());
+    let mut offsets = Vec::with_capacity(s.len());
+    for (offset, c) in s.char_indices().filter(|&(_, c)| !c.is_whitespace()) {
+        drex.push(c);
+        offsets.extend((0..c.len_utf8()).map(|_| offset));
+    }
+    (drex, offsets)
+}
+
+/// Parses a whitespace-free die roll expression into its terms and how they combine. Every term
+/// is validated, and parse failures are positioned in `drex`.
+pub(crate) fn parse_tree(drex: &str) -> Result<(Vec<DieRollTerm>, Expr), RollError> {
+    if drex.is_empty() {
+        return Err(RollError::EmptyExpression);
+    }
+    let mut parser = Parser { drex, tokens: tokenize(drex), pos: 0, depth: 0, terms: Vec::new() };
+    let expr = parser.sum()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok((parser.terms, expr)),
+        Some(&(Token::Close, _)) => Err(RollError::UnbalancedParentheses),
+        Some(&(_, at)) => Err(RollError::ParseFailure { position: at, fragment: drex[at..].to_string() }),
+    }
+}
+
+/// A token of a die roll expression.
+#[derive(Debug, Clone, Copy)]
+enum Token<'a> {
+    /// A die roll term, with its sign if it has one
+    Term(&'a str),
+    /// A sign that does not start a term, such as that of a parenthesized group, `true` when
+    /// the group is subtracted
+    Sign(bool),
+    Open,
+    Close,
+    Operator(Operator),
+    /// A character that cannot start any token, which no expression may contain
+    Unknown(&'a str),
+}
+
+impl<'a> Token<'a> {
+    /// Returns the length of the token in bytes.
+    fn len(&self) -> usize {
+        match *self {
+            Token::Term(t) | Token::Unknown(t) => t.len(),
+            _ => 1,
+        }
+    }
+}
+
+/// Splits a whitespace-free die roll expression into tokens, each with its byte offset.
+/// Characters that cannot start any token become `Token::Unknown`, for the parser to reject.
+fn tokenize(drex: &str) -> Vec<(Token<'_>, usize)> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < drex.len() {
+        let rest = &drex[i..];
+        let (token, len) = if let Some(end) = term_len(rest) {
+            (Token::Term(&rest[..end]), end)
+        } else {
+            let c = rest.chars().next().unwrap();
+            let token = match c {
+                '+' => Token::Sign(false),
+                '-' => Token::Sign(true),
+                '(' => Token::Open,
+                ')' => Token::Close,
+                '*' => Token::Operator(Operator::Multiply),
+                '/' => Token::Operator(Operator::Divide),
+                _ => Token::Unknown(&rest[..c.len_utf8()]),
+            };
+            (token, c.len_utf8())
+        };
+        tokens.push((token, i));
+        i += len;
+    }
+    tokens
+}
+
+/// A recursive descent parser over the tokens of a die roll expression, collecting the terms
+/// of the expression as it goes:
+///
+/// ```text
+/// sum     := product (signed-product)*
+/// product := operand (('*' | '/') operand)*
+/// operand := term | sign? '(' sum ')'
+/// ```
+///
+/// Every product of a sum after the first must start with a sign, which is how the parts of a
+/// sum are told apart.
+struct Parser<'a> {
+    drex: &'a str,
+    tokens: Vec<(Token<'a>, usize)>,
+    pos: usize,
+    /// How many parentheses are open at the current position
+    depth: usize,
+    terms: Vec<DieRollTerm>,
+}
+
+impl<'a> Parser<'a> {
+    fn sum(&mut self) -> Result<Expr, RollError> {
+        let mut parts = vec![self.product()?];
+        loop {
+            match self.tokens.get(self.pos) {
+                Some(&(Token::Term(t), _)) if t.starts_with(['+', '-']) => parts.push(self.product()?),
+                Some(&(Token::Sign(_), _)) => parts.push(self.product()?),
+                _ => return Ok(Expr::Sum(parts)),
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, RollError> {
+        let mut left = self.operand()?;
+        while let Some(&(Token::Operator(op), _)) = self.tokens.get(self.pos) {
+            self.pos += 1;
+            let right = self.operand()?;
+            left = Expr::Product(Box::new(left), op, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self) -> Result<Expr, RollError> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some((Token::Term(t), _)) => {
+                let term = DieRollTerm::try_parse(t)?;
+                term.check()?;
+                self.terms.push(term);
+                Ok(Expr::Term(self.terms.len() - 1))
+            }
+            Some((Token::Sign(negated), _)) => {
+                let group = match self.tokens.get(self.pos) {
+                    Some(&(Token::Open, _)) => self.operand()?,
+                    _ => return Err(self.failure()),
+                };
+                Ok(if negated { Expr::Negate(Box::new(group)) } else { group })
+            }
+            Some((Token::Open, _)) => {
+                self.depth += 1;
+                let group = self.sum()?;
+                match self.tokens.get(self.pos) {
+                    Some(&(Token::Close, _)) => {
+                        self.pos += 1;
+                        self.depth -= 1;
+                        Ok(group)
+                    }
+                    None => Err(RollError::UnbalancedParentheses),
+                    Some(_) => Err(self.failure()),
+                }
+            }
+            Some((Token::Close, _)) if self.depth == 0 => Err(RollError::UnbalancedParentheses),
+            _ => {
+                self.pos -= 1;
+                Err(self.failure())
+            }
+        }
+    }
+
+    /// Returns the error for an unexpected token at the current position, naming the rest of
+    /// the expression from there. If the tokens ran out, the failure is placed just after the
+    /// last of them.
+    fn failure(&self) -> RollError {
+        let at = match self.tokens.get(self.pos) {
+            Some(&(_, at)) => at,
+            None => self.tokens.last().map_or(0, |&(t, at)| at + t.len()),
+        };
+        RollError::ParseFailure { position: at, fragment: self.drex[at..].to_string() }
+    }
+}
+
+/// Parses a die roll expression for analysis that relies on the terms simply being added up,
+/// rejecting expressions that multiply or divide.
+pub(crate) fn parse_terms(s: &str) -> Result<Vec<DieRollTerm>, RollError> {
+    let (_, terms, expr) = parse_input(s)?;
+    if !expr.is_additive() {
+        return Err(RollError::Unsupported("only expressions that add and subtract can be analyzed"));
+    }
+    Ok(terms)
+}
+
+#[cfg(feature = "regex")]
+const TERM_PATTERN: &str = r"([+-]?\s*\d*[dD][fF]|[+-]?\s*\d*[dD]\[-?\d+(?:,-?\d+)*\]|[+-]?\s*\d*[dD](?:\d+|%)(?i:open\d*)?(?:!!|!(?i:p)?)?(?:(?i:ro)(?:[<>]=?\d+|=\d+)|(?i:r)\d+)?(?:(?i:min)\d+)?(?:(?i:max)\d+)?(?:(?i:k[hl])\d*)?(?:[<>]=?\d+|=\d+)?|[+-]?\s*\d+)";
+
+/// Returns the regex matching a single term at the start of a string, compiled the first time
+/// it is needed and shared from then on.
+#[cfg(feature = "regex")]
+fn term_regex() -> &'static Regex {
+    static TERM_REGEX: OnceLock<Regex> = OnceLock::new();
+    TERM_REGEX.get_or_init(|| Regex::new(&format!("^{}", TERM_PATTERN)).unwrap())
+}
+
+/// Returns the length in bytes of the die roll term at the start of a whitespace-free string,
+/// or `None` if it does not start with one.
+#[cfg(feature = "regex")]
+pub(crate) fn term_len(s: &str) -> Option<usize> {
+    term_regex().find(s).map(|m| m.end())
+}
+
+/// Returns the length in bytes of the die roll term at the start of a whitespace-free string,
+/// or `None` if it does not start with one. This scans for the same terms `TERM_PATTERN`
+/// matches, trying each kind of term in the same order: Fudge dice, custom dice, other dice
+/// with their suffixes and finally plain numbers.
+#[cfg(not(feature = "regex"))]
+pub(crate) fn term_len(s: &str) -> Option<usize> {
+    let b = s.as_bytes();
+    let start = if b.first().is_some_and(|&c| c == b'+' || c == b'-') { 1 } else { 0 };
+    let count = digits(b, start);
+    if b.get(count).is_some_and(|&c| c == b'd' || c == b'D') {
+        let i = count + 1;
+        if keyword(b, i, b"f").is_some() {
+            return Some(i + 1);
+        }
+        if let Some(end) = custom_faces(b, i) {
+            return Some(end);
+        }
+        let sides = if b.get(i) == Some(&b'%') { i + 1 } else { digits(b, i) };
+        if sides > i {
+            return Some(suffixes(b, sides));
+        }
+    }
+    if count > start {
+        Some(count)
+    } else {
+        None
+    }
+}
+
+/// Returns the index just past the run of digits starting at `i`, or `i` if there are none.
+#[cfg(not(feature = "regex"))]
+fn digits(b: &[u8], i: usize) -> usize {
+    i + b.get(i..).map_or(0, |rest| rest.iter().take_while(|c| c.is_ascii_digit()).count())
+}
+
+/// Returns the index just past `word` if it appears at `i`, ignoring case.
+#[cfg(not(feature = "regex"))]
+fn keyword(b: &[u8], i: usize, word: &[u8]) -> Option<usize> {
+    match b.get(i..i + word.len()) {
+        Some(w) if w.eq_ignore_ascii_case(word) => Some(i + word.len()),
+        _ => None,
+    }
+}
+
+/// Returns the index just past a number that must have at least one digit, optionally
+/// preceded by `prefix`.
+#[cfg(not(feature = "regex"))]
+fn number_after(b: &[u8], i: usize, prefix: &[u8]) -> Option<usize> {
+    let i = keyword(b, i, prefix)?;
+    let end = digits(b, i);
+    if end > i {
+        Some(end)
+    } else {
+        None
+    }
+}
+
+/// Returns the index just past the comparison (`<3`, `>=7`, `=6`, ...) at `i`, if there is one.
+#[cfg(not(feature = "regex"))]
+fn comparison(b: &[u8], i: usize) -> Option<usize> {
+    match b.get(i) {
+        Some(b'<') | Some(b'>') => number_after(b, i + 1, b"=").or_else(|| number_after(b, i + 1, b"")),
+        Some(b'=') => number_after(b, i + 1, b""),
+        _ => None,
+    }
+}
+
+/// Returns the index just past the bracketed faces of a custom die (`[2,4,6]`) at `i`, if there
+/// are any.
+#[cfg(not(feature = "regex"))]
+fn custom_faces(b: &[u8], i: usize) -> Option<usize> {
+    let mut i = keyword(b, i, b"[")?;
+    loop {
+        let face = if b.get(i) == Some(&b'-') { i + 1 } else { i };
+        i = number_after(b, face, b"")?;
+        match b.get(i) {
+            Some(b',') => i += 1,
+            Some(b']') => return Some(i + 1),
+            _ => return None,
+        }
+    }
+}
+
+/// Returns the index just past any suffixes of a die roll term starting at `i`, which are all
+/// optional but must come in order: open-ended, exploding, reroll, minimum, maximum, keep and
+/// success.
+#[cfg(not(feature = "regex"))]
+fn suffixes(b: &[u8], mut i: usize) -> usize {
+    if let Some(end) = keyword(b, i, b"open") {
+        i = digits(b, end);
+    }
+    if let Some(end) = keyword(b, i, b"!") {
+        i = keyword(b, end, b"!").or_else(|| keyword(b, end, b"p")).unwrap_or(end);
+    }
+    let reroll = keyword(b, i, b"ro").and_then(|end| comparison(b, end));
+    if let Some(end) = reroll.or_else(|| number_after(b, i, b"r")) {
+        i = end;
+    }
+    if let Some(end) = number_after(b, i, b"min") {
+        i = end;
+    }
+    if let Some(end) = number_after(b, i, b"max") {
+        i = end;
+    }
+    if let Some(end) = keyword(b, i, b"kh").or_else(|| keyword(b, i, b"kl")) {
+        i = digits(b, end);
+    }
+    comparison(b, i).unwrap_or(i)
+}
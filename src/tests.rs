@@ -1,6 +1,24 @@
+use std::collections::HashMap;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+
 use Roll;
 use DieRollTerm;
-use {roll_dice, roll_range, parse_die_roll_terms};
+use Comparison;
+use MAX_EXPLOSIONS;
+use Expr;
+use parser::term_len;
+use {roll_dice, is_valid, parse_expression, roll_dice_multi, roll_all, roll_all_strict, RollExpr, distribution, mean, min_total, max_total, roll_dice_with, roll_dice_seeded, RollError, d20_check, d20_check_with_options, CheckOptions, CheckResult, roll_dice_with_options, RollOptions, face_frequencies, RollTable, dice_for_target_average, roll_range, roll_range_inclusive, parse_die_roll_terms, variance, std_dev, roll_pool_with,
+     roll_ability_scores, roll_ability_scores_with, roll_pool_with_options, PoolOptions,
+     roll_totals, roll_dice_with_vars, PushYourLuck, Bust, expected_successes,
+     parse_partial, ParseStop, roll_attack_crit_range, are_equivalent,
+     max_single_die_value, exact_summary, exact_distribution,
+     roll_times, RollBuilder, probability_at_least, probabilities, average, roll_expression};
+
+/// Returns how the terms of a hand-built roll of `terms` terms that are all added up combine.
+fn additive(terms: usize) -> Expr {
+    Expr::Sum((0..terms).map(Expr::Term).collect())
+}
 
 #[test]
 fn die_roll_expression_parsed() {
@@ -9,8 +27,8 @@ fn die_roll_expression_parsed() {
     let nd = "-4d10+5".to_string();
     let mf = "50+2d8-1d4".to_string();
 
-    let pv = parse_die_roll_terms(&pd);
-    if let DieRollTerm::DieRoll { multiplier: m, sides: s } = pv[0] {
+    let pv = parse_die_roll_terms(&pd).unwrap();
+    if let DieRollTerm::DieRoll { multiplier: m, sides: s, .. } = pv[0] {
         assert_eq!(m, 3);
         assert_eq!(s, 12);
     }
@@ -18,8 +36,8 @@ fn die_roll_expression_parsed() {
         assert_eq!(n, 4);
     }
 
-    let nv = parse_die_roll_terms(&nd);
-    if let DieRollTerm::DieRoll { multiplier: m, sides: s } = nv[0] {
+    let nv = parse_die_roll_terms(&nd).unwrap();
+    if let DieRollTerm::DieRoll { multiplier: m, sides: s, .. } = nv[0] {
         assert_eq!(m, -4);
         assert_eq!(s, 10);
     }
@@ -27,15 +45,15 @@ fn die_roll_expression_parsed() {
         assert_eq!(n, 5);
     }
 
-    let mv = parse_die_roll_terms(&mf);
+    let mv = parse_die_roll_terms(&mf).unwrap();
     if let DieRollTerm::Modifier(n) = mv[0] {
         assert_eq!(n, 50);
     }
-    if let DieRollTerm::DieRoll { multiplier: m, sides: s } = mv[1] {
+    if let DieRollTerm::DieRoll { multiplier: m, sides: s, .. } = mv[1] {
         assert_eq!(m, 2);
         assert_eq!(s, 8);
     }
-    if let DieRollTerm::DieRoll { multiplier: m, sides: s } = mv[2] {
+    if let DieRollTerm::DieRoll { multiplier: m, sides: s, .. } = mv[2] {
         assert_eq!(m, -1);
         assert_eq!(s, 4);
     }
@@ -48,17 +66,17 @@ fn die_roll_term_parsed() {
     let mfy = "+7".to_string();
     let drt = DieRollTerm::parse(&drt);
     let mfy = DieRollTerm::parse(&mfy);
-    if let DieRollTerm::DieRoll { multiplier: m, sides: s } = drt {
+    if let DieRollTerm::DieRoll { multiplier: m, sides: s, .. } = drt {
         assert_eq!(m, 3);
         assert_eq!(s, 6);
     } else {
-        assert!(false);
+        panic!("expected a DieRoll term");
     }
 
     if let DieRollTerm::Modifier(n) = mfy {
         assert_eq!(n, 7);
     } else {
-        assert!(false);
+        panic!("expected a Modifier term");
     }
 }
 
@@ -69,16 +87,16 @@ fn die_roll_term_calculated() {
     let pm = DieRollTerm::parse("+7").evaluate();
     let nm = DieRollTerm::parse("-7").evaluate();
 
-    let dtr = DieRollTerm::calculate(dt);
+    let dtr = DieRollTerm::calculate(&dt);
     assert_eq!(dtr, 6);
 
-    let ntr = DieRollTerm::calculate(nt);
+    let ntr = DieRollTerm::calculate(&nt);
     assert_eq!(ntr, -4);
 
-    let pmr = DieRollTerm::calculate(pm);
+    let pmr = DieRollTerm::calculate(&pm);
     assert_eq!(pmr, 7);
 
-    let nmr = DieRollTerm::calculate(nm);
+    let nmr = DieRollTerm::calculate(&nm);
     assert_eq!(nmr, -7);
 }
 
@@ -134,10 +152,7 @@ fn roll_dice_produces_error_for_invalid_expression() {
     let s = "two plus two equals CHICKEN!";
     let r = roll_dice(s);
 
-    match r {
-        Ok(_) => assert!(false),
-        Err(_) => assert!(true),
-    }
+    assert!(r.is_err());
 }
 
 #[test]
@@ -155,16 +170,13 @@ fn result_range_roll_produces_result_in_range() {
 fn roll_range_min_max_switched() {
     let r = roll_range(12, 1);
 
-    match r {
-        Ok(_) => assert!(false),
-        Err(_) => assert!(true),
-    }
+    assert!(r.is_err());
 }
 
 #[test]
 fn iterator_yields_new_results() {
     let r = roll_dice("3d6");
-    let v: Vec<Roll> = r.unwrap().into_iter().take(6).collect();
+    let v: Vec<Roll> = r.unwrap().into_iter().take(6).collect::<Result<_, _>>().unwrap();
 
     assert_eq!(v.len(), 6);
     assert!(v[0].total >= 3 && v[0].total <= 18);
@@ -195,3 +207,1885 @@ fn roll_displays_properly() {
     let out = format!("{}", bigger_roll);
     assert_eq!(out, "3d1[1, 1, 1]-2d1[1, 1]-4 (Total: -3)");
 }
+
+#[test]
+fn variance_sums_independent_terms() {
+    let v = variance("2d6").unwrap();
+    assert!((v - 2.0 * 35.0 / 12.0).abs() < 1e-9);
+
+    assert!((variance("1d6").unwrap() - 2.9167).abs() < 1e-4);
+
+    let v = variance("1d6 + 5").unwrap();
+    assert!((v - 35.0 / 12.0).abs() < 1e-9);
+
+    assert_eq!(variance("-2d6").unwrap(), variance("2d6").unwrap());
+    assert_eq!(variance("+4-2").unwrap(), 0.0);
+    assert!(variance("lots of chickens").is_err());
+}
+
+#[test]
+fn std_dev_is_root_of_variance() {
+    let sd = std_dev("1d20").unwrap();
+    assert!((sd - 5.766).abs() < 1e-3);
+    // three dice bunch up around the middle, so 3d6+1 swings far less than 1d20
+    assert!(std_dev("3d6 + 1").unwrap() < sd / 1.5);
+}
+
+#[test]
+fn impossible_terms_abort_evaluation() {
+    assert!(roll_dice("1d0").is_err());
+    assert!(roll_dice("2d6 + 1d0 + 3").is_err());
+    assert!(variance("1d0").is_err());
+    assert_eq!(
+        DieRollTerm::parse("3d0").validate(),
+        Err("a die must have at least one side")
+    );
+    assert_eq!(
+        roll_dice("2d6 + 1d0").unwrap_err(),
+        RollError::InvalidTerm { term: "1d0".to_string(), reason: "a die must have at least one side" }
+    );
+    assert_eq!(
+        roll_dice("1d6!r1").unwrap_err(),
+        RollError::InvalidTerm { term: "1d6!r1".to_string(), reason: "exploding dice cannot be rerolled" }
+    );
+    assert_eq!(
+        roll_dice("1d20openmax5").unwrap_err(),
+        RollError::InvalidTerm {
+            term: "1d20openmax5".to_string(),
+            reason: "open-ended dice cannot have a maximum",
+        }
+    );
+    assert!(DieRollTerm::parse("3d6").validate().is_ok());
+}
+
+#[test]
+fn pool_counts_successes_with_closure() {
+    let (roll, successes) = roll_pool_with("5d1 + 1", |face| face >= 1).unwrap();
+    assert_eq!(roll.total, 6);
+    assert_eq!(successes, 5); // the +1 modifier is not a die
+
+    let (_, successes) = roll_pool_with("5d1", |face| face > 1).unwrap();
+    assert_eq!(successes, 0);
+
+    assert!(roll_pool_with("no dice here", |_| true).is_err());
+}
+
+#[test]
+fn crit_doubles_dice_but_not_modifiers() {
+    let roll = roll_dice("2d1 + 5 - 1d1").unwrap();
+    assert_eq!(roll.total, 6);
+    assert_eq!(roll.dice_only_total(), 1);
+
+    let crit = roll.crit_double_dice();
+    assert_eq!(crit.drex, "2d1+2d1+5-1d1-1d1");
+    assert_eq!(crit.total, 7);
+    assert_eq!(crit.dice_only_total(), 2);
+    assert_eq!(format!("{}", crit), "2d1[1, 1]+2d1[1, 1]+5-1d1[1]-1d1[1] (Total: 7)");
+}
+
+#[test]
+fn positive_sign_dice_groups_parse_and_display() {
+    let signed = parse_die_roll_terms("+2d6").unwrap();
+    if let DieRollTerm::DieRoll { multiplier: m, sides: s, .. } = signed[0] {
+        assert_eq!(m, 2);
+        assert_eq!(s, 6);
+    } else {
+        panic!("expected a DieRoll term");
+    }
+
+    let out = format!("{}", roll_dice("+2d1").unwrap());
+    assert_eq!(out, "2d1[1, 1] (Total: 2)");
+
+    let out = format!("{}", roll_dice("+2d1+3").unwrap());
+    assert_eq!(out, "2d1[1, 1]+3 (Total: 5)");
+
+    let out = format!("{}", roll_dice("+3+2d1").unwrap());
+    assert_eq!(out, "3+2d1[1, 1] (Total: 5)");
+
+    let out = format!("{}", roll_dice("-3+2d1").unwrap());
+    assert_eq!(out, "-3+2d1[1, 1] (Total: -1)");
+}
+
+#[test]
+fn natural_total_excludes_modifiers() {
+    let roll = roll_dice("1d1 + 5").unwrap();
+    assert_eq!(roll.natural_total(), 1);
+    assert_eq!(roll.total, 6);
+
+    let roll = roll_dice("3d1 - 2d1 + 4").unwrap();
+    assert_eq!(roll.natural_total(), 5);
+    assert_eq!(roll.dice_only_total(), 1);
+    assert_eq!(roll.total, 5);
+}
+
+#[test]
+fn open_ended_term_parsed_and_displayed() {
+    match DieRollTerm::parse("1d100open") {
+        DieRollTerm::DieRoll { multiplier: 1, sides: 100, open_ended: Some(5), .. } => {}
+        t => panic!("unexpected term {:?}", t),
+    }
+    match DieRollTerm::parse("2d20open2") {
+        DieRollTerm::DieRoll { multiplier: 2, sides: 20, open_ended: Some(2), .. } => {}
+        t => panic!("unexpected term {:?}", t),
+    }
+
+    assert_eq!(format!("{}", DieRollTerm::parse("1d100open")), "1d100open");
+    assert_eq!(format!("{}", DieRollTerm::parse("1d100open10")), "1d100open10");
+    assert_eq!(parse_die_roll_terms("1d100open+5").unwrap().len(), 2);
+}
+
+#[test]
+fn open_ended_dice_chain_in_both_directions() {
+    for _ in 0..500 {
+        let r = roll_dice("1d10open").unwrap();
+        let faces = &r.values[0].1;
+        let first = faces[0];
+        assert!((1..=10).contains(&first));
+
+        match first {
+            10 => assert!(faces[1..].iter().all(|&f| f >= 1)),
+            1 => assert!(faces[1..].iter().all(|&f| f <= -1)),
+            _ => assert_eq!(faces.len(), 1),
+        }
+        // every roll but the last in a chain reopened it by landing on the top face
+        if faces.len() > 1 {
+            assert!(faces[1..faces.len() - 1].iter().all(|&f| f.abs() == 10));
+        }
+        assert_eq!(r.total, faces.iter().map(|&f| i64::from(f)).sum::<i64>());
+    }
+}
+
+#[test]
+fn open_ended_dice_reject_overlapping_thresholds() {
+    assert!(roll_dice("1d2open").is_err());
+    assert!(roll_dice("1d10open5").is_err());
+    assert!(roll_dice("1d10open4").is_ok());
+    assert!(variance("1d100open").is_err());
+}
+
+#[test]
+fn ability_scores_paired_with_names() {
+    let names = ["STR", "DEX", "CON"];
+    let scores = roll_ability_scores(&names).unwrap();
+    assert_eq!(scores.len(), 3);
+    for (i, (name, roll)) in scores.iter().enumerate() {
+        assert_eq!(name, names[i]);
+        assert!(roll.total >= 3 && roll.total <= 18);
+    }
+
+    let scores = roll_ability_scores_with(&names, "2d1+8", false).unwrap();
+    assert!(scores.iter().all(|s| s.1.total == 10));
+
+    let scores = roll_ability_scores_with(&names, "1d20", true).unwrap();
+    assert!(scores[0].1.total >= scores[1].1.total);
+    assert!(scores[1].1.total >= scores[2].1.total);
+    assert_eq!(scores[0].0, "STR");
+
+    assert!(roll_ability_scores_with(&names, "four chickens", false).is_err());
+}
+
+#[test]
+fn pool_rerolled_once_on_zero_successes() {
+    let options = PoolOptions { reroll_pool_on_zero: true };
+
+    let pool = roll_pool_with_options("4d1", |face| face > 1, options).unwrap();
+    assert_eq!(pool.successes, 0);
+    assert!(pool.pool_rerolled);
+
+    let pool = roll_pool_with_options("4d1", |face| face == 1, options).unwrap();
+    assert_eq!(pool.successes, 4);
+    assert!(!pool.pool_rerolled);
+
+    let pool = roll_pool_with_options("4d1", |face| face > 1, PoolOptions::default()).unwrap();
+    assert!(!pool.pool_rerolled);
+}
+
+#[test]
+fn roll_totals_returns_only_totals() {
+    assert_eq!(roll_totals("2d1 + 3", 4).unwrap(), vec![5, 5, 5, 5]);
+    assert_eq!(roll_totals("-2d1", 2).unwrap(), vec![-2, -2]);
+    assert!(roll_totals("1d6", 0).unwrap().is_empty());
+    assert!(roll_totals("ferrets", 3).is_err());
+
+    let totals = roll_totals("3d6", 200).unwrap();
+    assert!(totals.iter().all(|t| (3..=18).contains(t)));
+}
+
+#[test]
+fn variables_substituted_into_expression() {
+    let mut vars = HashMap::new();
+    vars.insert("cantrip_dice", 3);
+    vars.insert("bonus", 4);
+
+    let r = roll_dice_with_vars("{cantrip_dice}d10", &vars).unwrap();
+    assert_eq!(r.drex, "3d10");
+    assert_eq!(r.values[0].1.len(), 3);
+
+    let r = roll_dice_with_vars("{ cantrip_dice }d1 + {bonus}", &vars).unwrap();
+    assert_eq!(r.drex, "3d1+4");
+    assert_eq!(r.total, 7);
+
+    vars.insert("penalty", -2);
+    let r = roll_dice_with_vars("1d1-{penalty}", &vars).unwrap();
+    assert_eq!(r.drex, "1d1-(-2)");
+    assert_eq!(r.total, 3);
+
+    assert!(roll_dice_with_vars("{level}d10", &vars).is_err());
+    assert!(roll_dice_with_vars("{cantrip_dice d10", &vars).is_err());
+}
+
+#[test]
+fn exploding_chains_grouped_in_display() {
+    let term = DieRollTerm::parse("2d10open");
+    let values = vec![10, 10, 7, 1, -10, -3];
+    assert_eq!(term.chains(&values), vec![&[10, 10, 7][..], &[1, -10, -3][..]]);
+    assert_eq!(term.chains(&[4, 5]), vec![&[4][..], &[5][..]]);
+
+    let roll = Roll {
+        drex: "2d10open+1".to_string(),
+        raw: "2d10open+1".to_string(),
+        label: None,
+        values: vec![(term, vec![10, 7, 4]), (DieRollTerm::parse("+1"), vec![1])],
+        total: 22,
+        metadata: HashMap::new(),
+        expr: additive(2),
+    };
+    assert_eq!(format!("{}", roll), "2d10open[10→7, 4]+1 (Total: 22)");
+}
+
+#[test]
+fn push_your_luck_accumulates_until_bust() {
+    let mut run = PushYourLuck::new(1, Bust::On(2)).unwrap();
+    assert!(!run.push());
+    assert!(!run.push());
+    assert!(!run.push());
+    assert_eq!(run.total(), 3);
+
+    let mut run = PushYourLuck::new(1, Bust::Duplicate).unwrap();
+    assert!(!run.push());
+    assert_eq!(run.total(), 1);
+    assert!(run.push());
+    assert!(run.is_bust());
+    assert_eq!(run.total(), 0);
+    assert_eq!(run.dice(), &[1, 1]);
+
+    // busted runs stay busted without rolling
+    assert!(run.push());
+    assert_eq!(run.dice().len(), 2);
+
+    let mut run = PushYourLuck::new(1, Bust::On(1)).unwrap();
+    assert!(run.push());
+
+    assert!(PushYourLuck::new(0, Bust::On(1)).is_err());
+}
+
+#[test]
+fn dice_pool_counts_successes() {
+    match DieRollTerm::parse("8d10>=7") {
+        DieRollTerm::DieRoll { multiplier: 8, sides: 10, success: Some(Comparison::AtLeast(7)), .. } => {}
+        t => panic!("unexpected term {:?}", t),
+    }
+    for t in &["6d10>=7", "6d10>7", "6d10<=2", "6d10<2", "6d10=10"] {
+        assert_eq!(&format!("{}", DieRollTerm::parse(t)), t);
+    }
+
+    assert_eq!(roll_dice("6d1>=1").unwrap().total, 6);
+    assert_eq!(roll_dice("6d1>1").unwrap().total, 0);
+    assert_eq!(roll_dice("4d1<=1 + 2").unwrap().total, 6);
+    assert_eq!(roll_dice("5d1=1 - 2d1=1").unwrap().total, 3);
+    assert!(roll_dice("1d100open>=50").is_err());
+}
+
+#[test]
+fn expected_successes_of_pools() {
+    assert!((expected_successes("8d10>=7").unwrap() - 3.2).abs() < 1e-9);
+    assert!((expected_successes("8d10>=7 + 2d6>3").unwrap() - 4.2).abs() < 1e-9);
+    assert!((expected_successes("8d10>=7 - 2d6>3").unwrap() - 2.2).abs() < 1e-9);
+    assert!(expected_successes("2d6 + 3").is_err());
+
+    assert!((variance("8d10>=7").unwrap() - 1.92).abs() < 1e-9);
+}
+
+#[test]
+fn partial_parse_reports_where_it_stopped() {
+    let p = parse_partial("2d6 + 3");
+    assert_eq!(p.terms.len(), 2);
+    assert_eq!(p.stopped, None);
+
+    let p = parse_partial("2d6 + ");
+    assert_eq!(p.terms.len(), 1);
+    assert_eq!(p.stopped, Some(ParseStop { position: 4, reason: "expecting a number" }));
+
+    let p = parse_partial("2d6 + 1d");
+    assert_eq!(p.terms.len(), 1);
+    assert_eq!(p.stopped, Some(ParseStop { position: 4, reason: "expecting the number of sides" }));
+
+    let p = parse_partial("6d10>=");
+    assert_eq!(p.terms.len(), 1);
+    assert_eq!(p.stopped, Some(ParseStop { position: 4, reason: "expecting a target number" }));
+
+    let p = parse_partial("1d20 chicken");
+    assert_eq!(p.terms.len(), 1);
+    assert_eq!(p.stopped, Some(ParseStop { position: 5, reason: "unexpected character" }));
+
+    let p = parse_partial("1d4+1d0");
+    assert_eq!(p.terms.len(), 1);
+    assert_eq!(p.stopped.unwrap().position, 3);
+
+    // positions are byte offsets, even after multi-byte whitespace
+    let p = parse_partial("1d6\u{a0}x");
+    assert_eq!(p.stopped, Some(ParseStop { position: 5, reason: "unexpected character" }));
+
+    let p = parse_partial("   ");
+    assert!(p.terms.is_empty());
+    assert_eq!(p.stopped, Some(ParseStop { position: 3, reason: "expecting a die roll term" }));
+}
+
+#[test]
+fn attack_crits_within_range() {
+    for _ in 0..100 {
+        let (roll, crit) = roll_attack_crit_range("1d20 + 7", 19).unwrap();
+        let natural = roll.values[0].1[0];
+        assert_eq!(crit, natural >= 19);
+    }
+    let (roll, crit) = roll_attack_crit_range("1d20", 2).unwrap();
+    assert_eq!(crit, roll.total != 1);
+
+    assert!(roll_attack_crit_range("1d20", 1).is_err());
+    assert!(roll_attack_crit_range("1d20", 21).is_err());
+    assert!(roll_attack_crit_range("2d20", 19).is_err());
+    assert!(roll_attack_crit_range("1d20+1d4", 19).is_err());
+    assert!(roll_attack_crit_range("1d12+3", 12).is_err());
+}
+
+#[test]
+fn success_margin_of_pool_rolls() {
+    let roll = roll_dice("5d1>=1").unwrap();
+    assert_eq!(roll.success_margin(3), Some(2));
+    assert_eq!(roll.success_margin(5), Some(0));
+    assert_eq!(roll.success_margin(8), Some(-3));
+
+    let roll = roll_dice("3d1>=1 + 2d1>1 + 1").unwrap();
+    assert_eq!(roll.success_margin(4), Some(0));
+
+    assert_eq!(roll_dice("3d6").unwrap().success_margin(1), None);
+    assert_eq!(roll_dice("3d1>=1 + 1d6").unwrap().success_margin(1), None);
+    assert_eq!(roll_dice("+3").unwrap().success_margin(1), None);
+}
+
+#[test]
+fn equivalent_expressions_after_normalization() {
+    assert_eq!(are_equivalent("2d6+3d6", "5d6"), Ok(true));
+    assert_eq!(are_equivalent("+2+3", "+5"), Ok(true));
+    assert_eq!(are_equivalent("1d8 + 2 + 1d4 + 1d8 - 1", "1d4+1+2d8"), Ok(true));
+    assert_eq!(are_equivalent("-1d4 - 1d4", "-2d4"), Ok(true));
+    assert_eq!(are_equivalent("3d10>=7 + 3d10>=7", "6d10>=7"), Ok(true));
+
+    assert_eq!(are_equivalent("2d6-1d6", "1d6"), Ok(false));
+    assert_eq!(are_equivalent("6d10>=7", "6d10>=8"), Ok(false));
+    assert_eq!(are_equivalent("1d100open", "1d100"), Ok(false));
+    assert_eq!(are_equivalent("2d6", "2d8"), Ok(false));
+
+    assert!(are_equivalent("2d6", "chickens").is_err());
+}
+
+#[test]
+fn best_and_worst_case_single_die() {
+    assert_eq!(max_single_die_value("2d6 + 1d12"), Ok(12));
+    assert_eq!(max_single_die_value("1d4 - 1d8 + 20"), Ok(8));
+    assert!(max_single_die_value("+5").is_err());
+    assert_eq!(max_single_die_value("1d6max4"), Ok(4));
+    assert_eq!(max_single_die_value("1d[-5,-3]"), Ok(-3));
+    assert_eq!(max_single_die_value("1d[1,100000]"), Ok(100_000));
+
+    for _ in 0..50 {
+        let roll = roll_dice("2d6 + 1d12").unwrap();
+        let best = roll.best_single_die().unwrap();
+        assert!((1..=12).contains(&best));
+        assert!(roll.values.iter().all(|v| v.1.iter().all(|&f| f <= best)));
+    }
+    assert_eq!(roll_dice("3d1 + 7").unwrap().best_single_die(), Some(1));
+    assert_eq!(roll_dice("7").unwrap().best_single_die(), None);
+}
+
+#[test]
+fn exact_summary_of_expression() {
+    let summary = exact_summary("2d6").unwrap();
+    assert_eq!(summary.min, 2);
+    assert_eq!(summary.max, 12);
+    assert_eq!(summary.mode, 7);
+    assert!((summary.mean - 7.0).abs() < 1e-9);
+    assert!((summary.variance - 70.0 / 12.0).abs() < 1e-9);
+
+    // 10 and 11 are equally likely on 3d6; the lowest is reported
+    assert_eq!(exact_summary("3d6").unwrap().mode, 10);
+
+    let summary = exact_summary("20 - 1d4").unwrap();
+    assert_eq!((summary.min, summary.max), (16, 19));
+    assert!((summary.mean - 17.5).abs() < 1e-9);
+
+    let summary = exact_summary("4d6>=5").unwrap();
+    assert_eq!((summary.min, summary.max), (0, 4));
+    assert!((summary.mean - 4.0 / 3.0).abs() < 1e-9);
+
+    assert!(exact_summary("1d100open").is_err());
+    assert!(exact_summary("127d255 + 127d255 + 127d255").is_err());
+}
+
+#[test]
+fn exact_distribution_sums_to_one() {
+    let dist = exact_distribution(&parse_die_roll_terms("2d6").unwrap()).unwrap();
+    assert_eq!(dist.len(), 11);
+    assert!((dist[&7] - 6.0 / 36.0).abs() < 1e-12);
+    assert!((dist.values().sum::<f64>() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn roll_times_is_bounded() {
+    let v: Vec<Roll> = roll_times("2d1 + 1", 4).unwrap().collect::<Result<_, _>>().unwrap();
+    assert_eq!(v.len(), 4);
+    assert!(v.iter().all(|r| r.total == 3));
+
+    assert_eq!(roll_times("1d6", 0).unwrap().count(), 0);
+    assert!(roll_times("chickens", 3).is_err());
+}
+
+#[test]
+fn reroll_once_keeps_second_roll() {
+    // every face of a d1 is below 3, yet each die is rerolled only once
+    let r = roll_dice("3d1ro<3").unwrap();
+    assert_eq!(r.values[0].1.len(), 6);
+    assert_eq!(r.total, 3);
+    assert_eq!(r.to_string(), "3d1ro<3[(1), 1, (1), 1, (1), 1] (Total: 3)");
+
+    for _ in 0..100 {
+        let r = roll_dice("2d6ro<3").unwrap();
+        let term = &r.values[0];
+        let kept: i32 = term.0.chains(&term.1).iter().map(|c| c[c.len() - 1]).sum();
+        assert_eq!(r.total, i64::from(kept));
+        assert!(term.0.chains(&term.1).iter().all(|c| c.len() == 1 || c[0] < 3));
+    }
+}
+
+#[test]
+fn reroll_once_parses_and_displays() {
+    let terms = parse_die_roll_terms("2d6ro<3+1d10ro=1>=8").unwrap();
+    assert_eq!(terms[0].to_string(), "2d6ro<3");
+    assert_eq!(terms[1].to_string(), "1d10ro=1>=8");
+    assert!(roll_dice("1d6openro<2").is_err());
+
+    // a d4 rerolling 1s settles on 2, 3 and 4 with probability 5/16 each
+    let summary = exact_summary("1d4ro<2").unwrap();
+    assert!((summary.mean - 46.0 / 16.0).abs() < 1e-9);
+}
+
+#[test]
+fn dice_for_target_average_rounds_up() {
+    assert_eq!(dice_for_target_average(6, 30.0), 9);
+    assert_eq!(dice_for_target_average(6, 31.5), 9);
+    assert_eq!(dice_for_target_average(20, 10.5), 1);
+    assert_eq!(dice_for_target_average(20, 10.6), 2);
+    assert_eq!(dice_for_target_average(1, 3.0), 3);
+    assert_eq!(dice_for_target_average(8, 0.0), 0);
+}
+
+#[test]
+fn roll_table_lookup() {
+    let table = RollTable::new(vec![(21..=50, "B"), (1..=20, "A"), (51..=100, "C")]).unwrap();
+    for _ in 0..100 {
+        let (roll, outcome) = table.roll_on().unwrap();
+        let expected = if roll <= 20 { "A" } else if roll <= 50 { "B" } else { "C" };
+        assert_eq!(outcome, expected);
+    }
+}
+
+#[test]
+fn roll_table_rejects_incomplete_tables() {
+    assert!(RollTable::new(vec![(1..=20, "A"), (22..=100, "B")]).is_err());
+    assert!(RollTable::new(vec![(1..=20, "A"), (20..=100, "B")]).is_err());
+    assert!(RollTable::new(vec![(1..=20, "A"), (21..=99, "B")]).is_err());
+    assert!(RollTable::new(vec![]).is_err());
+    assert_eq!(
+        RollTable::new(vec![(1..=i32::MAX, "A")]).unwrap_err(),
+        RollError::InvalidArgument("roll table ranges must not go past 100")
+    );
+    #[allow(clippy::reversed_empty_ranges)]
+    let empty = 50..=1;
+    assert_eq!(
+        RollTable::new(vec![(1..=100, "A"), (empty, "B")]).unwrap_err(),
+        RollError::InvalidArgument("roll table has an empty range")
+    );
+}
+
+#[test]
+fn term_exploded_and_rerolled() {
+    let r = roll_dice("2d1ro<2 + 1d6 + 3").unwrap();
+    assert!(r.term_rerolled(0));
+    assert!(!r.term_exploded(0));
+    assert!(!r.term_rerolled(1));
+    assert!(!r.term_rerolled(2));
+    assert!(!r.term_rerolled(3));
+
+    // a d3 open on 1 face explodes on a 3 and is never rerolled
+    for _ in 0..50 {
+        let r = roll_dice("1d3open1").unwrap();
+        assert_eq!(r.term_exploded(0), r.values[0].1.len() > 1);
+        assert!(!r.term_rerolled(0));
+    }
+}
+
+#[test]
+fn negative_terms_subtract_their_modified_result() {
+    // the reroll and the pool count happen before the sign is applied
+    let r = roll_dice("10 - 3d1ro<2").unwrap();
+    assert_eq!(r.values[1].1.len(), 6);
+    assert_eq!(r.total, 7);
+    assert_eq!(roll_dice("5 - 4d1>=1").unwrap().total, 1);
+
+    // an exploded chain is totalled and then subtracted as a whole
+    for _ in 0..50 {
+        let r = roll_dice("-1d3open1").unwrap();
+        let chain: i32 = r.values[0].1.iter().sum();
+        assert_eq!(r.total, -i64::from(chain));
+    }
+}
+
+#[test]
+fn display_signed_subtotals() {
+    let r = roll_dice("20-2d1").unwrap();
+    assert_eq!(r.subtotals(), vec![20, -2]);
+    assert_eq!(roll_dice("10-(1d1+2)").unwrap().subtotals(), vec![10, 1, 2]);
+    assert_eq!(format!("{:+}", r), "20-2d1[1, 1]=-2 (Total: 18)");
+    assert_eq!(format!("{}", r), "20-2d1[1, 1] (Total: 18)");
+}
+
+#[test]
+fn face_frequencies_counts_each_face() {
+    let freq = face_frequencies(4, 4000).unwrap();
+    assert_eq!(freq.keys().cloned().collect::<Vec<u16>>(), vec![1, 2, 3, 4]);
+    assert_eq!(freq.values().sum::<u32>(), 4000);
+    assert!(freq.values().all(|&n| n > 800 && n < 1200));
+
+    assert!(face_frequencies(20, 0).unwrap().is_empty());
+    assert!(face_frequencies(0, 10).is_err());
+}
+
+#[test]
+fn roll_metadata_travels_with_roll() {
+    let r = roll_dice("1d1+2").unwrap().with_tag("roller", "Mira").with_tag("roller", "Tam");
+    assert_eq!(r.metadata.len(), 1);
+    assert_eq!(r.metadata["roller"], "Tam");
+    assert_eq!(r.to_string(), "1d1[1]+2 (Total: 3)");
+    assert_eq!(r.crit_double_dice().metadata["roller"], "Tam");
+}
+
+#[test]
+fn roll_delta_from_previous() {
+    let low = roll_dice("2d1+1").unwrap();
+    let high = roll_dice("2d1+4").unwrap();
+    assert_eq!(high.delta(&low), 3);
+    assert_eq!(low.delta(&high), -3);
+    assert!(high.improved_over(&low));
+    assert!(!low.improved_over(&high));
+    assert!(!low.improved_over(&low));
+
+    assert_eq!(low.term_deltas(&low), Some(vec![0, 0]));
+    assert_eq!(high.term_deltas(&low), None);
+}
+
+#[test]
+fn roll_matches_and_glitches() {
+    let r = Roll {
+        drex: "5d6>=5".to_string(),
+        raw: "5d6>=5".to_string(),
+        label: None,
+        values: vec![(DieRollTerm::parse("5d6>=5"), vec![1, 4, 1, 6, 4])],
+        total: 1,
+        metadata: HashMap::new(),
+        expr: additive(1),
+    };
+    assert_eq!(r.matches(), vec![(1, 2), (4, 2)]);
+    assert!(!r.is_glitch());
+
+    let r = roll_dice("3d1+2d2").unwrap();
+    assert_eq!(r.matches()[0].0, 1);
+    assert!(r.matches()[0].1 >= 3);
+    assert!(r.is_glitch());
+
+    let r = roll_dice("1d6 + 5").unwrap();
+    assert!(r.matches().is_empty());
+    assert!(!roll_dice("7").unwrap().is_glitch());
+}
+
+#[test]
+fn roll_explosion_count() {
+    let term = DieRollTerm::parse("3d10open");
+    let r = Roll {
+        drex: "3d10open+1".to_string(),
+        raw: "3d10open+1".to_string(),
+        label: None,
+        values: vec![(term, vec![10, 10, 7, 1, -10, -3, 5]), (DieRollTerm::parse("+1"), vec![1])],
+        total: 11,
+        metadata: HashMap::new(),
+        expr: additive(2),
+    };
+    assert_eq!(r.explosion_count(), 4);
+
+    assert_eq!(roll_dice("2d1ro<2 + 4d6").unwrap().explosion_count(), 0);
+    for _ in 0..50 {
+        let r = roll_dice("2d3open1").unwrap();
+        assert_eq!(r.explosion_count(), r.values[0].1.len() - 2);
+    }
+}
+
+#[test]
+fn roll_with_work_budget() {
+    let tight = RollOptions { work_budget: 6 };
+    assert_eq!(roll_dice_with_options("3d1 + 3d1 + 20", tight).unwrap().total, 26);
+    assert!(roll_dice_with_options("3d1 + 4d1", tight).is_err());
+    // rerolls count against the budget too
+    assert!(roll_dice_with_options("4d1ro<2", tight).is_err());
+    assert!(roll_dice_with_options("3d1ro<2", tight).is_ok());
+
+    assert_eq!(RollOptions::default().work_budget, 10_000);
+}
+
+#[test]
+fn d20_check_degrees_of_success() {
+    for _ in 0..200 {
+        let check = d20_check(3, 12);
+        let natural = check.roll.total - 3;
+        let expected = match natural {
+            1 => CheckResult::CriticalFailure,
+            20 => CheckResult::CriticalSuccess,
+            _ if check.roll.total >= 12 => CheckResult::Success,
+            _ => CheckResult::Failure,
+        };
+        assert_eq!(check.result, expected);
+    }
+}
+
+#[test]
+fn d20_check_configurable_crits() {
+    // with no critical rules, only the DC decides
+    let plain = CheckOptions { crit_min: 21, fumble_max: 0 };
+    assert_eq!(d20_check_with_options(-20, 1, plain).result, CheckResult::Failure);
+    assert_eq!(d20_check_with_options(20, 21, plain).result, CheckResult::Success);
+
+    // every natural roll fumbles or crits, and fumbles take precedence
+    let all_crits = CheckOptions { crit_min: 1, fumble_max: 0 };
+    assert_eq!(d20_check_with_options(-20, 100, all_crits).result, CheckResult::CriticalSuccess);
+    let all_fumbles = CheckOptions { crit_min: 1, fumble_max: 20 };
+    assert_eq!(d20_check_with_options(20, 0, all_fumbles).result, CheckResult::CriticalFailure);
+}
+
+#[test]
+fn roll_sum_top_n_across_terms() {
+    let r = Roll {
+        drex: "2d6+3d8+4".to_string(),
+        raw: "2d6+3d8+4".to_string(),
+        label: None,
+        values: vec![
+            (DieRollTerm::parse("2d6"), vec![6, 2]),
+            (DieRollTerm::parse("+3d8"), vec![5, 7, 1]),
+            (DieRollTerm::parse("+4"), vec![4]),
+        ],
+        total: 25,
+        metadata: HashMap::new(),
+        expr: additive(3),
+    };
+    assert_eq!(r.sum_top_n(3), 18);
+    assert_eq!(r.sum_top_n(0), 0);
+    assert_eq!(r.sum_top_n(10), 21);
+}
+
+#[test]
+fn roll_with_advantage_and_disadvantage() {
+    for _ in 0..50 {
+        let r = roll_dice("1d20+5").unwrap();
+        let natural = r.total - 5;
+
+        let adv = r.with_advantage().unwrap();
+        assert_eq!(adv.total - 5, i64::from(adv.values[0].1[0]));
+        assert!(adv.total >= r.total);
+
+        let dis = r.with_disadvantage().unwrap();
+        assert_eq!(dis.total - 5, i64::from(dis.values[0].1[0]));
+        assert!(dis.total - 5 <= natural);
+    }
+
+    assert!(roll_dice("2d20").unwrap().with_advantage().is_err());
+    assert!(roll_dice("1d20+1d4").unwrap().with_disadvantage().is_err());
+    assert!(roll_dice("5").unwrap().with_advantage().is_err());
+}
+
+#[test]
+fn roll_spend_fate_on_lowest() {
+    let r = Roll {
+        drex: "2d6+1d8-1".to_string(),
+        raw: "2d6+1d8-1".to_string(),
+        label: None,
+        values: vec![
+            (DieRollTerm::parse("2d6"), vec![3, 2]),
+            (DieRollTerm::parse("+1d8"), vec![2]),
+            (DieRollTerm::parse("-1"), vec![-1]),
+        ],
+        total: 6,
+        metadata: HashMap::new(),
+        expr: additive(3),
+    };
+    let fated = r.spend_fate_on_lowest();
+    assert_eq!(fated.values[0].1, vec![3, 6]);
+    assert_eq!(fated.values[1].1, vec![2]);
+    assert_eq!(fated.total, 10);
+
+    let fated = fated.spend_fate_on_lowest();
+    assert_eq!(fated.values[1].1, vec![8]);
+    assert_eq!(fated.total, 16);
+
+    assert_eq!(roll_dice("4").unwrap().spend_fate_on_lowest().total, 4);
+}
+
+#[test]
+fn keep_highest_sums_only_kept_dice() {
+    let term = DieRollTerm::parse("4d6kh3");
+    assert_eq!(DieRollTerm::calculate(&(term.clone(), vec![3, 1, 6, 4])), 13);
+    assert_eq!(DieRollTerm::calculate(&(DieRollTerm::parse("2d20kh"), vec![7, 18])), 18);
+    assert_eq!(DieRollTerm::calculate(&(DieRollTerm::parse("-4d6kh3"), vec![3, 1, 6, 4])), -13);
+    assert_eq!(term.to_string(), "4d6kh3");
+    assert_eq!(DieRollTerm::parse("2d20KH").to_string(), "2d20kh1");
+
+    for _ in 0..50 {
+        let r = roll_dice("4d6kh3 + 1").unwrap();
+        let mut faces = r.values[0].1.clone();
+        assert_eq!(faces.len(), 4);
+        faces.sort();
+        assert_eq!(r.total, 1 + faces[1..].iter().map(|&f| i64::from(f)).sum::<i64>());
+    }
+}
+
+#[test]
+fn keep_highest_statistics() {
+    // 2d20 keeping the highest averages 13.825
+    let summary = exact_summary("2d20kh1").unwrap();
+    assert!((summary.mean - 13.825).abs() < 1e-9);
+    assert_eq!(summary.mode, 20);
+    assert!((exact_summary("4d6kh3").unwrap().mean - 15869.0 / 1296.0).abs() < 1e-9);
+    assert!((variance("2d6kh2").unwrap() - variance("2d6").unwrap()).abs() < 1e-9);
+
+    assert!(!are_equivalent("2d20kh1 + 2d20kh1", "4d20kh2").unwrap());
+    assert!(are_equivalent("2d20kh1 + 2d20kh1 + 1", "1 + 2d20kh1 + 2d20kh1").unwrap());
+}
+
+#[test]
+fn keep_lowest_and_display_dropped_dice() {
+    let term = DieRollTerm::parse("2d20kl1");
+    assert_eq!(term.to_string(), "2d20kl1");
+    let roll = Roll {
+        drex: "2d20kl1".to_string(),
+        raw: "2d20kl1".to_string(),
+        label: None,
+        values: vec![(term.clone(), vec![18, 7])],
+        total: DieRollTerm::calculate(&(term, vec![18, 7])),
+        metadata: HashMap::new(),
+        expr: additive(1),
+    };
+    assert_eq!(roll.to_string(), "2d20kl1[(18), 7] (Total: 7)");
+    assert_eq!(DieRollTerm::calculate(&(DieRollTerm::parse("4d6kl2"), vec![3, 1, 6, 1])), 2);
+
+    let r = roll_dice("3d1kh2").unwrap();
+    assert_eq!(r.to_string(), "3d1kh2[1, 1, (1)] (Total: 2)");
+    assert!((exact_summary("2d20kl1").unwrap().mean - 7.175).abs() < 1e-9);
+}
+
+#[test]
+fn keep_more_than_rolled_is_an_error() {
+    assert!(roll_dice("2d20kh3").is_err());
+    assert!(roll_dice("-2d20kl3").is_err());
+    assert!(roll_dice("2d20kl2").is_ok());
+}
+
+#[test]
+fn exploding_dice_chain_on_max() {
+    let term = DieRollTerm::parse("3d6!");
+    assert_eq!(term.to_string(), "3d6!");
+    let values = vec![6, 6, 2, 3, 6, 1];
+    assert_eq!(term.chains(&values), vec![&[6, 6, 2][..], &[3][..], &[6, 1][..]]);
+    assert_eq!(DieRollTerm::calculate(&(term.clone(), values.clone())), 24);
+
+    let roll = Roll {
+        drex: "3d6!".to_string(),
+        raw: "3d6!".to_string(),
+        label: None,
+        values: vec![(term, values)],
+        total: 24,
+        metadata: HashMap::new(),
+        expr: additive(1),
+    };
+    assert_eq!(roll.to_string(), "3d6![6→6→2, 3, 6→1] (Total: 24)");
+    assert_eq!(roll.explosion_count(), 3);
+    assert!(roll.term_exploded(0));
+
+    for _ in 0..100 {
+        let r = roll_dice("2d2!").unwrap();
+        let chains = r.values[0].0.chains(&r.values[0].1);
+        assert_eq!(chains.len(), 2);
+        assert!(chains.iter().all(|c| c[..c.len() - 1].iter().all(|&f| f == 2) && c[c.len() - 1] == 1
+            || c.len() == MAX_EXPLOSIONS + 1));
+        assert_eq!(r.total, r.values[0].1.iter().map(|&f| i64::from(f)).sum::<i64>());
+    }
+}
+
+#[test]
+fn exploding_one_sided_die_is_an_error() {
+    assert!(roll_dice("1d1!").is_err());
+    assert!(roll_dice("1d6open!").is_err());
+    assert!(variance("1d6!").is_err());
+    assert!(exact_summary("2d6! + 1").is_err());
+}
+
+#[test]
+fn reroll_at_or_below_threshold() {
+    let term = DieRollTerm::parse("4d6r2");
+    assert_eq!(term.to_string(), "4d6r2");
+    assert_eq!(DieRollTerm::parse("4d6ro<=2").to_string(), "4d6r2");
+    assert_eq!(DieRollTerm::calculate(&(term, vec![1, 5, 2, 1, 6, 3])), 5 + 1 + 6 + 3);
+
+    // every face of a d1 qualifies, but each die is still rerolled only once
+    let r = roll_dice("1d1r1").unwrap();
+    assert_eq!(r.values[0].1, vec![1, 1]);
+    assert_eq!(r.total, 1);
+
+    for _ in 0..50 {
+        let r = roll_dice("4d6R1 + 2").unwrap();
+        assert_eq!(r.term_rerolled(0), r.values[0].1.len() > 4);
+        assert!(r.total >= 6 && r.total <= 26);
+    }
+}
+
+#[test]
+fn dice_with_many_sides() {
+    for _ in 0..50 {
+        let r = roll_dice("1d1000").unwrap();
+        assert!(r.total >= 1 && r.total <= 1000);
+    }
+    assert_eq!(DieRollTerm::parse("2d65535").to_string(), "2d65535");
+    assert_eq!(max_single_die_value("1d6 + 1d1000").unwrap(), 1000);
+    assert!(roll_dice("1d40000").unwrap().total <= 40000);
+}
+
+#[test]
+fn large_dice_counts() {
+    let r = roll_dice("200d6").unwrap();
+    assert_eq!(r.values[0].1.len(), 200);
+    assert!(r.total >= 200 && r.total <= 1200);
+    assert_eq!(r.values[0].0.to_string(), "200d6");
+
+    let r = roll_dice("10 - 300d1").unwrap();
+    assert_eq!(r.values[1].1.len(), 300);
+    assert_eq!(r.total, -290);
+    assert_eq!(DieRollTerm::parse("-300d1").to_string(), "-300d1");
+    assert_eq!(roll_dice("1000d1kh999").unwrap().total, 999);
+}
+
+#[test]
+fn huge_totals_do_not_overflow() {
+    // 32767 dice showing 65535 each total over 2^31
+    let term = DieRollTerm::parse("32767d65535");
+    let values = vec![65535; 32767];
+    assert_eq!(DieRollTerm::calculate(&(term, values)), 32767 * 65535);
+
+    let r = roll_dice("300d1000 + 300d1000").unwrap();
+    assert!(r.total >= 600 && r.total <= 600_000);
+}
+
+#[test]
+fn errors_name_what_went_wrong() {
+    assert_eq!(roll_dice("   ").unwrap_err(), RollError::EmptyExpression);
+    assert_eq!(
+        roll_dice("chickens").unwrap_err(),
+        RollError::ParseFailure { position: 0, fragment: "chickens".to_string() }
+    );
+    assert_eq!(roll_dice("1d99999").unwrap_err(), RollError::Overflow);
+    assert_eq!(roll_range(5, 1).unwrap_err(), RollError::InvalidRange { min: 5, max: 1 });
+    assert_eq!(
+        roll_dice_with_options("1000d6", RollOptions { work_budget: 10 }).unwrap_err(),
+        RollError::BudgetExceeded
+    );
+    assert_eq!(
+        RollError::InvalidRange { min: 5, max: 1 }.to_string(),
+        "Invalid range: min (5) must be less than or equal to max (1)."
+    );
+}
+
+#[test]
+fn seeded_rolls_are_reproducible() {
+    let faces = |r: Roll| r.values.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+    let first = roll_dice_seeded("10d20! + 3d6ro<2 + 1d100open - 2", 7).unwrap();
+    let again = roll_dice_seeded("10d20! + 3d6ro<2 + 1d100open - 2", 7).unwrap();
+    assert_eq!(first.total, again.total);
+    assert_eq!(faces(first), faces(again));
+
+    let rolls: Vec<_> = (0..20).map(|seed| faces(roll_dice_seeded("10d20", seed).unwrap())).collect();
+    assert!(rolls.iter().any(|v| *v != rolls[0]));
+}
+
+#[test]
+fn custom_rng_supplies_every_die() {
+    // a generator that always yields zero, so every die lands on its lowest face
+    struct Zeroes;
+    impl Rng for Zeroes {
+        fn next_u32(&mut self) -> u32 {
+            0
+        }
+    }
+
+    let roll = roll_dice_with("4d6 + 1d20 - 2", &mut Zeroes).unwrap();
+    assert_eq!(roll.total, 3);
+    assert!(roll.values[..2].iter().all(|v| v.1.iter().all(|&f| f == 1)));
+}
+
+#[test]
+fn fudge_dice_total_between_minus_and_plus_count() {
+    for roll in roll_dice("4dF").unwrap().into_iter().take(200) {
+        let roll = roll.unwrap();
+        assert!(roll.total >= -4 && roll.total <= 4);
+        assert!(roll.values[0].1.iter().all(|f| (-1..=1).contains(f)));
+    }
+    let r = roll_dice("2dF + 3").unwrap();
+    assert_eq!(r.values[0].0.to_string(), "2dF");
+    assert!(r.to_string().starts_with("2dF["));
+    assert_eq!(exact_summary("4dF").unwrap().mean, 0.0);
+    assert!(are_equivalent("2dF+2dF", "4dF").unwrap());
+    assert!((variance("3dF").unwrap() - 2.0).abs() < 1e-9);
+}
+
+#[test]
+fn percentile_dice_roll_a_d100() {
+    for expr in &["1d%", "1d00"] {
+        let r = roll_dice(expr).unwrap();
+        assert!(r.total >= 1 && r.total <= 100);
+    }
+    let r = roll_dice("2d%kh1 + 1d00>=50").unwrap();
+    assert_eq!(r.drex, "2d%kh1+1d00>=50");
+    assert_eq!(r.values[0].0.to_string(), "2d100kh1");
+    assert_eq!(r.values[1].0.to_string(), "1d100>=50");
+    assert_eq!(max_single_die_value("1d%").unwrap(), 100);
+}
+
+#[test]
+fn missing_dice_count_means_one_die() {
+    let r = roll_dice("d20").unwrap();
+    assert!(r.total >= 1 && r.total <= 20);
+    assert_eq!(r.values[0].0.to_string(), "1d20");
+
+    let r = roll_dice("d1 + 5 - d1 + d%").unwrap();
+    assert_eq!(r.values.len(), 4);
+    assert_eq!(r.subtotals()[..3], [1, 5, -1]);
+    assert!(are_equivalent("d6 + d6", "2d6").unwrap());
+    assert_eq!(parse_partial("2d6 + d").stopped.unwrap().reason, "expecting the number of sides");
+}
+
+#[test]
+fn multiplication_and_division_bind_tighter_than_signs() {
+    assert_eq!(roll_dice("1d1*10").unwrap().total, 10);
+    assert_eq!(roll_dice("4d1/2").unwrap().total, 2);
+    assert_eq!(roll_dice("7/2").unwrap().total, 3);
+    assert_eq!(roll_dice("-7/2").unwrap().total, -3);
+    assert_eq!(roll_dice("10 - 4d1 / 2 * 3").unwrap().total, 4);
+
+    let r = roll_dice("2d6+1d4*3").unwrap();
+    let subtotals = r.subtotals();
+    assert_eq!(r.total, subtotals[0] + subtotals[1] * 3);
+    assert!(r.to_string().contains("]*3 (Total: "));
+
+    assert_eq!(roll_dice("1d6/0").unwrap_err(), RollError::DivisionByZero);
+    assert!(roll_dice("*3").is_err());
+    assert!(roll_dice("1d6*").is_err());
+    assert!(variance("1d6*2").is_err());
+    assert_eq!(roll_totals("2d1*3", 5).unwrap(), vec![6; 5]);
+
+    assert_eq!(parse_partial("1d6 * 10").terms.len(), 2);
+    assert_eq!(parse_partial("1d6 *").stopped.unwrap().reason, "expecting a number");
+    assert_eq!(parse_partial("1d6 **").stopped.unwrap().reason, "unexpected character");
+
+    let crit = roll_dice("1d1*10+2").unwrap().crit_double_dice();
+    assert_eq!(crit.drex, "(1d1+1d1)*10+2");
+    assert_eq!(crit.to_string(), "(1d1[1]+1d1[1])*10+2 (Total: 22)");
+}
+
+#[test]
+fn parentheses_group_terms() {
+    assert_eq!(roll_dice("((1d4))").unwrap().values.len(), 1);
+    assert_eq!(roll_dice("2*(1d1+3)").unwrap().total, 8);
+    assert_eq!(roll_dice("(1d1+1)*2").unwrap().total, 4);
+    assert_eq!(roll_dice("10-(2d1+1)").unwrap().total, 7);
+    assert_eq!(roll_dice("12/(6/2)").unwrap().total, 4);
+    assert_eq!(roll_dice("-(3)*2").unwrap().total, -6);
+
+    let r = roll_dice("((2d1+3))*(1+1)").unwrap();
+    assert_eq!(r.total, 10);
+    assert_eq!(r.to_string(), "(2d1[1, 1]+3)*(1+1) (Total: 10)");
+    assert_eq!(roll_dice("12/(6/2)").unwrap().to_string(), "12/(6/2) (Total: 4)");
+    assert_eq!(roll_dice("5-(1d1+1)").unwrap().to_string(), "5-(1d1[1]+1) (Total: 3)");
+
+    assert_eq!(roll_dice("(1d6+1").unwrap_err(), RollError::UnbalancedParentheses);
+    assert_eq!(roll_dice("1d6+1)").unwrap_err(), RollError::UnbalancedParentheses);
+    assert_eq!(roll_dice(")1d6(").unwrap_err(), RollError::UnbalancedParentheses);
+    assert!(roll_dice("()").is_err());
+    assert!(roll_dice("(1d6)(1d4)").is_err());
+    assert!(variance("-(1d6+1)").is_err());
+
+    assert_eq!(parse_partial("2 * (1d6 + 1)").terms.len(), 3);
+    assert!(parse_partial("2 * (1d6 + 1)").stopped.is_none());
+    assert_eq!(parse_partial("2 * (1d6 + 1").stopped.unwrap().reason, "expecting ')'");
+}
+
+#[test]
+fn pool_display_marks_successes() {
+    let r = Roll {
+        drex: "6d10>=7".to_string(),
+        raw: "6d10>=7".to_string(),
+        label: None,
+        values: vec![(DieRollTerm::parse("6d10>=7"), vec![3, 9, 7, 2, 10, 5])],
+        total: 3,
+        metadata: HashMap::new(),
+        expr: additive(1),
+    };
+    assert_eq!(r.to_string(), "6d10>=7[3, 9*, 7*, 2, 10*, 5] (Total: 3 successes)");
+
+    assert_eq!(roll_dice("4d1>=1").unwrap().to_string(), "4d1>=1[1*, 1*, 1*, 1*] (Total: 4 successes)");
+    assert_eq!(roll_dice("3d1>1 + 1").unwrap().to_string(), "3d1>1[1, 1, 1]+1 (Total: 1 success)");
+    assert_eq!(roll_dice("2d1<=1 + 1d1").unwrap().to_string(), "2d1<=1[1*, 1*]+1d1[1] (Total: 3)");
+}
+
+#[test]
+fn bounds_computed_without_rolling() {
+    assert_eq!(min_total("2d6+3"), Ok(5));
+    assert_eq!(max_total("2d6+3"), Ok(15));
+    assert_eq!((min_total("-2d6").unwrap(), max_total("-2d6").unwrap()), (-12, -2));
+    assert_eq!((min_total("4d6kh3").unwrap(), max_total("4d6kh3").unwrap()), (3, 18));
+    assert_eq!((min_total("6d10>=7").unwrap(), max_total("6d10>=7").unwrap()), (0, 6));
+    assert_eq!((min_total("3d1>=1").unwrap(), max_total("3d6>6").unwrap()), (3, 0));
+    assert_eq!((min_total("4dF").unwrap(), max_total("4dF").unwrap()), (-4, 4));
+    assert_eq!((min_total("(1d6+1)*2").unwrap(), max_total("(1d6+1)*2").unwrap()), (4, 14));
+    assert_eq!((min_total("12/1d4").unwrap(), max_total("12/1d4").unwrap()), (3, 12));
+    assert_eq!((min_total("6/(1d3-2)").unwrap(), max_total("6/(1d3-2)").unwrap()), (-6, 6));
+    assert!(max_total("3d6!").is_err());
+}
+
+#[test]
+fn mean_computed_without_rolling() {
+    assert_eq!(mean("1d6"), Ok(3.5));
+    assert_eq!(mean("2d6+1"), Ok(8.0));
+    assert_eq!(mean("-1d4 + 2dF"), Ok(-2.5));
+    assert_eq!(mean("(1d6+1)*2"), Ok(9.0));
+    assert!((mean("8d10>=7").unwrap() - 3.2).abs() < 1e-9);
+    assert!((mean("4d6kh3").unwrap() - exact_summary("4d6kh3").unwrap().mean).abs() < 1e-9);
+    assert!((mean("1d6r1").unwrap() - exact_summary("1d6r1").unwrap().mean).abs() < 1e-9);
+    // an exploding d6 averages 3.5 × 6/5 = 4.2, give or take the cap on explosions
+    assert!((mean("1d6!").unwrap() - 4.2).abs() < 1e-9);
+    assert!(mean("1d100open").is_err());
+    assert!(mean("1d6/2").is_err());
+}
+
+#[test]
+fn distribution_tallies_sampled_totals() {
+    let counts = distribution("3d6 + 1d4*2", 2000).unwrap();
+    assert_eq!(counts.values().sum::<u32>(), 2000);
+    let (low, high) = (min_total("3d6 + 1d4*2").unwrap(), max_total("3d6 + 1d4*2").unwrap());
+    assert!(counts.keys().all(|&t| t >= low && t <= high));
+
+    assert_eq!(distribution("2d1", 10).unwrap().into_iter().collect::<Vec<_>>(), vec![(2, 10)]);
+    assert!(distribution("1d6", 0).unwrap().is_empty());
+    assert!(distribution("chickens", 10).is_err());
+}
+
+#[test]
+fn roll_expr_rolls_without_reparsing() {
+    let e = RollExpr::parse("2d6 + 1d4*2 - 1").unwrap();
+    for r in e.clone().into_iter().take(50) {
+        let r = r.unwrap();
+        assert!(r.total >= 3 && r.total <= 19);
+        assert_eq!(r.drex, "2d6+1d4*2-1");
+    }
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let first = e.roll_with(&mut rng).unwrap();
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    assert_eq!(e.roll_with(&mut rng).unwrap().to_string(), first.to_string());
+    assert!(RollExpr::parse("1d6 + (2").is_err());
+
+    // iterating a roll rerolls its terms, never the text of its expression
+    let r = Roll {
+        drex: "chickens".to_string(),
+        raw: "chickens".to_string(),
+        label: None,
+        values: vec![(DieRollTerm::DieRoll { multiplier: 2, sides: 1, open_ended: None, exploding: false,
+                                             compounding: false, penetrating: false, reroll: None, min: None,
+                                             max: None, keep: None, success: None }, vec![1, 1])],
+        total: 2,
+        metadata: HashMap::new(),
+        expr: additive(1),
+    };
+    let rolls: Vec<Roll> = r.into_iter().take(3).collect::<Result<_, _>>().unwrap();
+    assert_eq!(rolls.len(), 3);
+    assert!(rolls.iter().all(|r| r.total == 2));
+}
+
+#[test]
+fn parses_many_expressions_with_a_shared_regex() {
+    for n in 1..500 {
+        let drex = format!("{}d{}+{}", n % 7 + 1, n % 20 + 1, n % 5);
+        assert_eq!(parse_die_roll_terms(&drex).unwrap().len(), 2);
+    }
+    assert!(parse_die_roll_terms("chickens").is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn rolls_round_trip_through_json() {
+    let r = roll_dice("(2d6kh1 + 4dF)*2 - 3").unwrap().with_tag("roller", "Mira");
+    let json = ::serde_json::to_string(&r).unwrap();
+    let back: Roll = ::serde_json::from_str(&json).unwrap();
+
+    assert_eq!(back.drex, r.drex);
+    assert_eq!(back.total, r.total);
+    assert_eq!(back.to_string(), r.to_string());
+    assert_eq!(back.metadata["roller"], "Mira");
+    let faces = |r: &Roll| r.values.iter().map(|v| v.1.clone()).collect::<Vec<_>>();
+    assert_eq!(faces(&back), faces(&r));
+
+    let json = ::serde_json::to_string(&RollExpr::parse("1d6*10").unwrap()).unwrap();
+    let e: RollExpr = ::serde_json::from_str(&json).unwrap();
+    assert_eq!(e.roll().unwrap().total % 10, 0);
+}
+
+#[test]
+fn terms_and_expressions_parse_from_strings() {
+    match "3d6".parse::<DieRollTerm>().unwrap() {
+        DieRollTerm::DieRoll { multiplier, sides, exploding, .. } => {
+            assert_eq!((multiplier, sides, exploding), (3, 6, false));
+        }
+        other => panic!("expected a die roll, got {:?}", other),
+    }
+    match "- 4".parse::<DieRollTerm>().unwrap() {
+        DieRollTerm::Modifier(n) => assert_eq!(n, -4),
+        other => panic!("expected a modifier, got {:?}", other),
+    }
+    let garbage = "chickens".parse::<DieRollTerm>();
+    assert_eq!(garbage.unwrap_err(), RollError::ParseFailure { position: 0, fragment: "chickens".to_string() });
+    assert!("2d6+1".parse::<DieRollTerm>().is_err());
+    assert!("2d6kh3".parse::<DieRollTerm>().is_err());
+    assert_eq!("".parse::<DieRollTerm>().unwrap_err(), RollError::EmptyExpression);
+
+    let exprs: Result<Vec<RollExpr>, RollError> = ["1d20+5", "2d6*10"].iter().map(|s| s.parse()).collect();
+    assert_eq!(exprs.unwrap().len(), 2);
+    assert!("(1d20".parse::<RollExpr>().is_err());
+}
+
+#[test]
+fn rerolling_replaces_the_results() {
+    let mut r = roll_dice("2d6 + 1d1*3").unwrap().with_tag("for", "damage");
+    let fresh = r.rolled().unwrap();
+    assert_eq!(fresh.drex, r.drex);
+    assert_eq!(fresh.metadata["for"], "damage");
+
+    for _ in 0..20 {
+        r.reroll().unwrap();
+        assert!(r.total >= 5 && r.total <= 15);
+        assert_eq!(r.total, r.values[0].1.iter().sum::<i32>() as i64 + 3);
+    }
+
+    r.drex = "chickens".to_string();
+    let total = r.total;
+    assert!(r.reroll().is_err());
+    assert_eq!(r.total, total);
+}
+
+#[test]
+fn dice_and_modifier_totals_split_the_total() {
+    let r = roll_dice("3d1+5").unwrap();
+    assert_eq!((r.dice_only_total(), r.modifier_total()), (3, 5));
+
+    let r = roll_dice("2d1 - 1d1 + 4 - 6").unwrap();
+    assert_eq!((r.dice_only_total(), r.modifier_total()), (1, -2));
+    assert_eq!(r.dice_only_total() + r.modifier_total(), r.total);
+
+    assert_eq!(roll_dice("4d1").unwrap().modifier_total(), 0);
+
+    // a subtracted group is not split by sign
+    let r = roll_dice("10-(1d1+2)").unwrap();
+    assert_eq!((r.dice_only_total(), r.modifier_total(), r.total), (1, 12, 7));
+}
+
+#[test]
+fn critical_and_fumble_detection() {
+    let r = roll_dice("1d1+5").unwrap();
+    assert!(r.is_critical() && r.is_fumble());
+    assert!(r.term_critical(0) && !r.term_critical(1) && !r.term_critical(2));
+
+    let d20 = DieRollTerm::parse("1d20");
+    let roll_of = |faces: Vec<Vec<i32>>| Roll {
+        drex: "1d20+1d20".to_string(),
+        raw: "1d20+1d20".to_string(),
+        label: None,
+        values: faces.into_iter().map(|f| (d20.clone(), f)).collect(),
+        total: 0,
+        metadata: HashMap::new(),
+        expr: additive(2),
+    };
+    let r = roll_of(vec![vec![7], vec![20]]);
+    assert!(r.is_critical() && !r.is_fumble());
+    assert!(!r.term_critical(0) && r.term_critical(1));
+    let r = roll_of(vec![vec![1], vec![12]]);
+    assert!(!r.is_critical() && r.is_fumble());
+    assert!(r.term_fumbled(0) && !r.term_fumbled(1));
+
+    // the dropped die of a roll with advantage does not count
+    let r = Roll {
+        drex: "2d20kh1".to_string(),
+        raw: "2d20kh1".to_string(),
+        label: None,
+        values: vec![(DieRollTerm::parse("2d20kh1"), vec![1, 15])],
+        total: 15,
+        metadata: HashMap::new(),
+        expr: additive(1),
+    };
+    assert!(!r.is_fumble());
+}
+
+#[test]
+fn rolls_order_by_total() {
+    let mut rolls: Vec<Roll> = ["3d1", "1d1-4", "5", "2d1+2"].iter().map(|s| roll_dice(s).unwrap()).collect();
+    rolls.sort();
+    let totals: Vec<i64> = rolls.iter().map(|r| r.total).collect();
+    assert_eq!(totals, vec![-3, 3, 4, 5]);
+    assert_eq!(rolls.iter().max().unwrap().drex, "5");
+    assert!(rolls[0] < rolls[1]);
+    assert_eq!(roll_dice("3d1").unwrap(), roll_dice("1+2").unwrap());
+}
+
+#[test]
+fn cloned_rolls_are_independent() {
+    let mut r = roll_dice("3d6 + 2").unwrap().with_tag("for", "damage");
+    let copy = r.clone();
+    r.values[0].1 = vec![9, 9, 9];
+    r.total = 29;
+    r.metadata.clear();
+
+    assert_eq!(copy.total, copy.values[0].1.iter().sum::<i32>() as i64 + 2);
+    assert!(copy.values[0].1.iter().all(|&f| (1..=6).contains(&f)));
+    assert_eq!(copy.metadata["for"], "damage");
+}
+
+#[test]
+fn terms_can_key_a_hash_map() {
+    let mut means = HashMap::new();
+    for t in parse_die_roll_terms("1d6+2d6+1d6+1d6!").unwrap() {
+        let mean = t.mean().unwrap();
+        means.insert(t, mean);
+    }
+    assert_eq!(means.len(), 3);
+    assert_eq!(means[&DieRollTerm::parse("1d6")], 3.5);
+    assert_eq!(DieRollTerm::parse("1d%"), DieRollTerm::parse("1d100"));
+    assert!(DieRollTerm::parse("1d6") != DieRollTerm::parse("1d6!"));
+}
+
+#[test]
+fn alternate_format_leaves_out_the_dice() {
+    let r = roll_dice("5d1 + (1d1+2)*2").unwrap();
+    assert_eq!(format!("{:#}", r), "5d1+(1d1+2)*2 (Total: 11)");
+    assert_eq!(format!("{}", r), "5d1[1, 1, 1, 1, 1]+(1d1[1]+2)*2 (Total: 11)");
+
+    let r = roll_dice("6d10>=7").unwrap();
+    assert!(!format!("{:#}", r).contains('['));
+    assert!(format!("{:#}", r).ends_with("successes)") || format!("{:#}", r).ends_with("success)"));
+}
+
+#[test]
+fn all_dice_flattens_the_dice_terms() {
+    let r = roll_dice("3d6 + 5 - 2d8 + 4dF").unwrap();
+    let dice = r.all_dice();
+    assert_eq!(dice.len(), 3 + 2 + 4);
+    assert_eq!(&dice[..3], r.dice_for_term(0).unwrap());
+    assert_eq!(&dice[3..5], r.dice_for_term(2).unwrap());
+    assert!(dice[..5].iter().all(|&f| (1..=8).contains(&f)));
+
+    assert_eq!(r.dice_for_term(1), None);
+    assert_eq!(r.dice_for_term(4), None);
+    assert!(roll_dice("5 + 2").unwrap().all_dice().is_empty());
+}
+
+#[test]
+fn batches_roll_each_expression_independently() {
+    let results = roll_all(&["1d1+1", "", "2d1", "1d6+(", "-3"]);
+    let totals: Vec<Option<i64>> = results.iter().map(|r| r.as_ref().ok().map(|r| r.total)).collect();
+    assert_eq!(totals, vec![Some(2), None, Some(2), None, Some(-3)]);
+    assert_eq!(results[1].as_ref().unwrap_err(), &RollError::EmptyExpression);
+    assert!(roll_all(&[]).is_empty());
+
+    assert_eq!(roll_all_strict(&["1d1", "4"]).unwrap().len(), 2);
+    assert_eq!(roll_all_strict(&["1d1", "", "chickens"]).unwrap_err(), RollError::EmptyExpression);
+}
+
+#[test]
+fn multi_roll_expressions_roll_separately() {
+    let rolls = roll_dice_multi("1d1+1; 2d1").unwrap();
+    let totals: Vec<i64> = rolls.iter().map(|r| r.total).collect();
+    assert_eq!(totals, vec![2, 2]);
+    assert_eq!(rolls[1].drex, "2d1");
+
+    assert_eq!(roll_dice_multi("3, 1d1 ,-2").unwrap().len(), 3);
+    assert_eq!(roll_dice_multi("1d1").unwrap().len(), 1);
+    assert_eq!(roll_dice_multi("1d1; ;2").unwrap_err(), RollError::EmptyExpression);
+    assert_eq!(roll_dice_multi("1d1;").unwrap_err(), RollError::EmptyExpression);
+    assert!(roll_dice_multi("1d1; chickens").is_err());
+}
+
+#[test]
+fn multi_rolls_display_a_line_each() {
+    let rolls = roll_dice_multi("1d1+4 [attack]; 2d1 [damage]").unwrap();
+    assert_eq!(rolls.grand_total(), 7);
+    assert_eq!(
+        rolls.to_string(),
+        "1d1[1]+4 [attack] (Total: 5)\n2d1[1, 1] [damage] (Total: 2)\n(Grand Total: 7)"
+    );
+    assert_eq!(rolls.into_iter().map(|r| r.total).collect::<Vec<_>>(), vec![5, 2]);
+}
+
+#[test]
+fn zero_dice_contribute_nothing() {
+    let r = roll_dice("0d6 + 3").unwrap();
+    assert!(r.values[0].1.is_empty());
+    assert_eq!(r.total, 3);
+    assert_eq!(r.to_string(), "0d6[]+3 (Total: 3)");
+    assert_eq!(roll_dice("0d20").unwrap().total, 0);
+    assert_eq!((min_total("0d6+3").unwrap(), max_total("0d6+3").unwrap()), (3, 3));
+    assert_eq!(mean("0d6").unwrap(), 0.0);
+    assert_eq!(roll_dice("0d6kh1").unwrap_err(),
+               RollError::InvalidTerm { term: "0d6kh1".to_string(), reason: "cannot keep more dice than are rolled" });
+    assert_eq!(roll_dice("0d0").unwrap_err(),
+               RollError::InvalidTerm { term: "0d0".to_string(), reason: "a die must have at least one side" });
+}
+
+#[test]
+fn totals_are_computed_from_borrowed_values() {
+    let r = roll_dice("3d1 + 2d1*4 - 5").unwrap();
+    assert_eq!(r.total, 3 + 8 - 5);
+    assert_eq!(r.subtotals(), vec![3, 2, 4, -5]);
+    assert_eq!((r.dice_only_total(), r.modifier_total()), (5, -1));
+    assert_eq!(DieRollTerm::calculate(&r.values[1]), 2);
+}
+
+#[test]
+fn expressions_parse_into_terms_without_rolling() {
+    let terms = parse_expression("2d6 + 5").unwrap();
+    assert_eq!(terms, vec![DieRollTerm::parse("2d6"), DieRollTerm::Modifier(5)]);
+    let dice: i16 = terms.iter().map(|t| match *t {
+        DieRollTerm::DieRoll { multiplier, .. } => multiplier,
+        _ => 0,
+    }).sum();
+    assert_eq!(dice, 2);
+
+    assert_eq!(parse_expression("(1d20 - 1d4) * 2").unwrap().len(), 3);
+    assert_eq!(parse_expression(" ").unwrap_err(), RollError::EmptyExpression);
+    assert_eq!(parse_expression("1d6+(2").unwrap_err(), RollError::UnbalancedParentheses);
+}
+
+#[test]
+fn validity_matches_what_can_be_rolled() {
+    for drex in &["3d6", "d20 + 5", "-2", "4d6kh3 + (1d4+1)*2", "6d10>=7", "4dF", "1d%"] {
+        assert!(is_valid(drex), "{} should be valid", drex);
+        assert!(roll_dice(drex).is_ok());
+    }
+    for drex in &["", "   ", "\t\n", "chickens", "1d0", "2d6kh3", "(1d6", "1d6)", "999d6x99999"] {
+        assert!(!is_valid(drex), "{:?} should not be valid", drex);
+        assert!(roll_dice(drex).is_err());
+    }
+}
+
+#[test]
+fn rolls_keep_the_expression_as_entered() {
+    let r = roll_dice("2d6 +  5").unwrap();
+    assert_eq!(r.raw, "2d6 +  5");
+    assert_eq!(r.drex, "2d6+5");
+
+    let again = r.rolled().unwrap();
+    assert_eq!(again.raw, "2d6 +  5");
+    assert_eq!(RollExpr::parse("1d4 - 1").unwrap().roll().unwrap().raw, "1d4 - 1");
+    assert!(r.into_iter().take(2).all(|r| r.unwrap().raw == "2d6 +  5"));
+
+    let crit = roll_dice("1d6 + 2").unwrap().crit_double_dice();
+    assert_eq!(crit.raw, crit.drex);
+}
+
+#[test]
+fn labels_are_kept_and_displayed() {
+    let r = roll_dice("2d1+3 [fire  damage]").unwrap();
+    assert_eq!(r.total, 5);
+    assert_eq!(r.drex, "2d1+3");
+    assert_eq!(r.label, Some("fire  damage".to_string()));
+    assert_eq!(r.to_string(), "2d1[1, 1]+3 [fire  damage] (Total: 5)");
+    assert_eq!(format!("{:#}", r), "2d1+3 [fire  damage] (Total: 5)");
+    assert_eq!(r.rolled().unwrap().label, r.label);
+
+    let r = roll_dice("1d1 [to hit]  ").unwrap();
+    assert_eq!(r.label.as_deref(), Some("to hit"));
+    assert_eq!(roll_dice("1d1").unwrap().label, None);
+    assert_eq!(roll_dice(" [just a label]").unwrap_err(), RollError::EmptyExpression);
+}
+
+#[test]
+fn parse_failures_point_at_the_offending_text() {
+    let input = "2d6 + nope";
+    match roll_dice(input).unwrap_err() {
+        RollError::ParseFailure { position, fragment } => {
+            assert_eq!(position, input.find("nope").unwrap());
+            assert_eq!(fragment, "nope");
+        }
+        e => panic!("expected a parse failure, got {:?}", e),
+    }
+
+    let failure = |s: &str| match parse_expression(s).unwrap_err() {
+        RollError::ParseFailure { position, fragment } => (position, fragment),
+        e => panic!("expected a parse failure, got {:?}", e),
+    };
+    assert_eq!(failure("1d6 *  "), (7, "".to_string()));
+    assert_eq!(failure("1d6 + (1d4) 1d8"), (12, "1d8".to_string()));
+    assert_eq!(failure("  1d20 -"), (8, "".to_string()));
+    assert_eq!(
+        "3d6 x".parse::<DieRollTerm>().unwrap_err(),
+        RollError::ParseFailure { position: 4, fragment: "x".to_string() }
+    );
+
+    assert_eq!(
+        roll_dice("1d20 +").unwrap_err().to_string(),
+        "Invalid die roll expression: unexpected end at position 6."
+    );
+    assert_eq!(
+        roll_dice("1d20 + (3) 4").unwrap_err().to_string(),
+        "Invalid die roll expression: could not parse '4' at position 11."
+    );
+}
+
+#[test]
+fn text_outside_the_terms_is_rejected() {
+    assert_eq!(
+        roll_dice("3d6 chicken").unwrap_err(),
+        RollError::ParseFailure { position: 4, fragment: "chicken".to_string() }
+    );
+    assert!(roll_dice("3d6").is_ok());
+    assert!(roll_dice("1d20 + 5 to hit").is_err());
+    assert!(roll_dice("2d6 & 3").is_err());
+    assert!(roll_dice("1d20 ✓").is_err());
+    assert!(!is_valid("x1d20"));
+    assert_eq!(roll_dice("1d20 + 5 [to hit]").unwrap().label, Some("to hit".to_string()));
+}
+
+#[test]
+fn minimum_raises_low_dice() {
+    let term = DieRollTerm::parse("2d6min3");
+    assert_eq!(term.to_string(), "2d6min3");
+    assert_eq!(DieRollTerm::calculate(&(term.clone(), vec![1, 5])), 8);
+    assert_eq!(DieRollTerm::calculate(&(term.clone(), vec![2, 3])), 6);
+
+    let r = Roll {
+        drex: "2d6min3".to_string(),
+        raw: "2d6min3".to_string(),
+        label: None,
+        values: vec![(term, vec![1, 5])],
+        total: 8,
+        metadata: HashMap::new(),
+        expr: additive(1),
+    };
+    assert_eq!(r.to_string(), "2d6min3[1↑3, 5] (Total: 8)");
+
+    for _ in 0..20 {
+        let r = roll_dice("4d6MIN3").unwrap();
+        assert!(r.total >= 12 && r.total <= 24);
+    }
+    assert_eq!(roll_dice("2d1min1").unwrap().total, 2);
+    assert_eq!((min_total("2d6min3").unwrap(), max_total("2d6min3").unwrap()), (6, 12));
+    assert!((mean("1d6min3").unwrap() - 4.0).abs() < 1e-9);
+    assert!(roll_dice("1d6min7").is_err());
+    assert!(roll_dice("1d6min0").is_err());
+    assert!(roll_dice("1d20openmin2").is_err());
+}
+
+#[test]
+fn maximum_caps_high_dice() {
+    let term = DieRollTerm::parse("3d8min2max6");
+    assert_eq!(term.to_string(), "3d8min2max6");
+    assert_eq!(DieRollTerm::calculate(&(term.clone(), vec![1, 8, 4])), 2 + 6 + 4);
+    assert_eq!(term.format_values(&[1, 8, 4]), "[1↑2, 8↓6, 4]");
+
+    for _ in 0..20 {
+        let r = roll_dice("3d8max6").unwrap();
+        assert!(r.total >= 3 && r.total <= 18);
+    }
+    assert_eq!((min_total("3d8min2max6").unwrap(), max_total("3d8min2max6").unwrap()), (6, 18));
+    assert!((mean("1d8max6").unwrap() - 33.0 / 8.0).abs() < 1e-9);
+
+    assert_eq!(
+        roll_dice("3d8min6max2").unwrap_err(),
+        RollError::InvalidTerm {
+            term: "3d8min6max2".to_string(),
+            reason: "the minimum cannot be greater than the maximum",
+        }
+    );
+    assert!(roll_dice("1d8max9").is_err());
+    assert!(roll_dice("1d8max2min1").is_err());
+}
+
+#[test]
+fn roll_range_accepts_the_extreme_bounds() {
+    assert_eq!(roll_range(i32::MAX, i32::MAX).unwrap(), i32::MAX);
+    assert_eq!(roll_range(i32::MIN, i32::MIN).unwrap(), i32::MIN);
+    assert!(roll_range(i32::MAX - 1, i32::MAX).unwrap() >= i32::MAX - 1);
+    roll_range(i32::MIN, i32::MAX).unwrap();
+
+    assert_eq!(roll_range_inclusive(7..=7).unwrap(), 7);
+    assert!((1..=3).contains(&roll_range_inclusive(1..=3).unwrap()));
+    let (low, high) = (3, 1);
+    assert_eq!(roll_range_inclusive(low..=high).unwrap_err(), RollError::InvalidRange { min: 3, max: 1 });
+}
+
+#[test]
+fn custom_dice_roll_their_listed_faces() {
+    assert_eq!(roll_dice("d[5]").unwrap().total, 5);
+    assert_eq!(roll_dice("-2d[-3]").unwrap().total, 6);
+    for _ in 0..20 {
+        let r = roll_dice("3d[2,3,3,4,4,5]").unwrap();
+        assert!(r.total >= 6 && r.total <= 15);
+        assert!(r.values[0].1.iter().all(|f| [2, 3, 4, 5].contains(f)));
+        assert!(r.to_string().starts_with("3d[2,3,3,4,4,5]["));
+        assert!((-2..=-1).contains(&roll_dice("1d[-1,-2]").unwrap().total));
+    }
+
+    let summary = exact_summary("2d[1,1,4]").unwrap();
+    assert_eq!((summary.min, summary.max, summary.mode), (2, 8, 2));
+    assert!((summary.mean - 4.0).abs() < 1e-9);
+    assert!((summary.variance - 4.0).abs() < 1e-9);
+    assert!(are_equivalent("1d[3,1]+1d[1,3]", "2d[1,3]").unwrap());
+    // faces compare as a multiset: order is ignored but repeats are not
+    assert!(are_equivalent("2d[1,2]", "2d[2,1]").unwrap());
+    assert!(!are_equivalent("2d[1,2]", "2d[1,2,2]").unwrap());
+
+    assert!(roll_dice("1d[]").is_err());
+    assert!(roll_dice("1d[1,]").is_err());
+}
+
+#[test]
+fn custom_dice_are_not_labels_or_separators() {
+    let r = roll_dice("1d[2,4] [fire]").unwrap();
+    assert_eq!(r.label, Some("fire".to_string()));
+    assert!(r.total == 2 || r.total == 4);
+    assert_eq!(roll_dice("1d[7]").unwrap().label, None);
+
+    let rolls = roll_dice_multi("1d[1,3,5]; 1d6").unwrap();
+    assert_eq!(rolls.len(), 2);
+    assert_eq!(rolls[0].drex, "1d[1,3,5]");
+}
+
+#[test]
+fn compounding_dice_add_their_chain_into_one_die() {
+    // generators that always land a d6 on its lowest and on its highest face
+    struct Lowest;
+    impl Rng for Lowest {
+        fn next_u32(&mut self) -> u32 {
+            0
+        }
+    }
+    struct Highest;
+    impl Rng for Highest {
+        fn next_u32(&mut self) -> u32 {
+            5
+        }
+    }
+
+    let low = roll_dice_with("2d6!!", &mut Lowest).unwrap();
+    assert_eq!(low.values[0].1, vec![1, 1]);
+    assert_eq!(low.total, 2);
+    assert!(!low.term_exploded(0));
+
+    // every roll comes up 6, so each die compounds until the cap on explosions
+    let high = roll_dice_with("1d6!!", &mut Highest).unwrap();
+    assert_eq!(high.values[0].1, vec![6 * (MAX_EXPLOSIONS as i32 + 1)]);
+    assert!(high.term_exploded(0));
+    assert_eq!(high.explosion_count(), MAX_EXPLOSIONS);
+    assert_eq!(low.explosion_count(), 0);
+
+    // 6 + 6 + 3 exploded twice, and 6 + 1 once
+    let r = Roll {
+        values: vec![(DieRollTerm::parse("2d6!!"), vec![15, 7])],
+        ..roll_dice("2d6!!").unwrap()
+    };
+    assert_eq!(r.explosion_count(), 3);
+
+    let term = DieRollTerm::parse("2d6!!");
+    assert_eq!(term.to_string(), "2d6!!");
+    assert_eq!(term.format_values(&[14, 3]), "[14, 3]");
+    assert!(!are_equivalent("2d6!!", "2d6!").unwrap());
+    assert!(roll_dice("1d1!!").is_err());
+    assert!(roll_dice("1d6!!!").is_err());
+}
+
+#[test]
+fn compounding_means_clamp_the_whole_chain() {
+    // a capped chain counts 4 whenever it reaches 4, explosions or not
+    assert!((mean("1d6!!max4").unwrap() - 3.0).abs() < 1e-9);
+    assert!((mean("1d6!!min3").unwrap() - (3.0 * 3.0 + 4.0 + 5.0 + 10.2) / 6.0).abs() < 1e-9);
+    for e in &["1d6!!max4", "1d6!!min3", "2d4!!min2max3"] {
+        assert!((mean(e).unwrap() - average(e, 100_000).unwrap()).abs() < 0.05, "{}", e);
+    }
+}
+
+#[test]
+fn compounding_dice_charge_every_draw_to_the_budget() {
+    // generators that always land a d2 on its lowest and on its highest face
+    struct Lowest;
+    impl Rng for Lowest {
+        fn next_u32(&mut self) -> u32 {
+            0
+        }
+    }
+    struct Highest;
+    impl Rng for Highest {
+        fn next_u32(&mut self) -> u32 {
+            1
+        }
+    }
+
+    assert_eq!(roll_expression("1d2!!", 1, &mut Lowest).unwrap().total, 1);
+    // the die compounds into a single face, but drew more than once
+    assert_eq!(roll_expression("1d2!!", 1, &mut Highest).unwrap_err(), RollError::BudgetExceeded);
+    assert!(roll_expression("1d2!!", MAX_EXPLOSIONS, &mut Highest).is_err());
+    assert!(roll_expression("1d2!!", MAX_EXPLOSIONS + 1, &mut Highest).is_ok());
+}
+
+#[test]
+fn penetrating_dice_count_one_less_after_the_first_roll() {
+    let term = DieRollTerm::parse("2d6!p");
+    assert_eq!(term.to_string(), "2d6!p");
+    let values = vec![6, 6, 2, 3];
+    assert_eq!(term.chains(&values), vec![&[6, 6, 2][..], &[3][..]]);
+    assert_eq!(DieRollTerm::calculate(&(term.clone(), values.clone())), 6 + 5 + 1 + 3);
+    assert_eq!(term.format_values(&values), "[6→6↓5→2↓1, 3]");
+
+    // a generator that always lands a d6 on its highest face, so the die penetrates until the
+    // cap on explosions
+    struct Highest;
+    impl Rng for Highest {
+        fn next_u32(&mut self) -> u32 {
+            5
+        }
+    }
+    let r = roll_dice_with("1d6!p", &mut Highest).unwrap();
+    assert_eq!(r.values[0].1, vec![6; MAX_EXPLOSIONS + 1]);
+    assert_eq!(r.total, 6 + 5 * MAX_EXPLOSIONS as i64);
+    assert_eq!(r.explosion_count(), MAX_EXPLOSIONS);
+
+    assert!((mean("1d6!p").unwrap() - 4.0).abs() < 1e-6);
+    assert_eq!(DieRollTerm::parse("1d6!P").to_string(), "1d6!p");
+    assert!(roll_dice("1d1!p").is_err());
+    assert!(roll_dice("1d6!!p").is_err());
+}
+
+#[test]
+fn uppercase_and_spaced_dice_roll_like_lowercase() {
+    let upper = roll_dice_seeded("3D6", 11).unwrap();
+    let lower = roll_dice_seeded("3d6", 11).unwrap();
+    assert_eq!(upper.values, lower.values);
+    assert_eq!(upper.total, lower.total);
+
+    let messy = roll_dice_seeded("3 D 6 + 4", 11).unwrap();
+    assert_eq!(messy.values[0], lower.values[0]);
+    assert_eq!(messy.total, lower.total + 4);
+    assert_eq!(messy.values[0].0.to_string(), "3d6");
+}
+
+#[test]
+fn uppercase_d_parses_like_lowercase() {
+    let r = roll_dice("3D6").unwrap();
+    assert_eq!(r.values[0].0, DieRollTerm::parse("3d6"));
+    assert!(r.total >= 3 && r.total <= 18);
+
+    let r = roll_dice("3D6+2").unwrap();
+    assert_eq!(r.values.len(), 2);
+    assert!(r.total >= 5 && r.total <= 20);
+
+    let r = roll_dice("-2D8").unwrap();
+    assert_eq!(r.values[0].0, DieRollTerm::parse("-2d8"));
+    assert!(r.total >= -16 && r.total <= -2);
+}
+
+#[test]
+fn built_expressions_roll_like_parsed_ones() {
+    let built = RollBuilder::new().die(3, 6).modifier(5).die(2, 10).build().unwrap();
+    let parsed = RollExpr::parse("3d6+5+2d10").unwrap();
+    let a = built.roll_with(&mut XorShiftRng::from_seed([5, 6, 7, 8])).unwrap();
+    let b = parsed.roll_with(&mut XorShiftRng::from_seed([5, 6, 7, 8])).unwrap();
+    assert_eq!(a.drex, "3d6+5+2d10");
+    assert_eq!(a.values, b.values);
+    assert_eq!(a.total, b.total);
+    assert_eq!(a.to_string(), b.to_string());
+
+    let subtracted = RollBuilder::new().modifier(-2).die(-2, 8).build().unwrap().roll().unwrap();
+    assert_eq!(subtracted.drex, "-2-2d8");
+    assert!(subtracted.total >= -18 && subtracted.total <= -4);
+
+    assert_eq!(RollBuilder::new().build().unwrap_err(), RollError::EmptyExpression);
+    assert!(RollBuilder::new().die(1, 0).build().is_err());
+}
+
+#[test]
+fn term_len_matches_whole_terms_only() {
+    // these hold whether terms are matched by the regex or by the hand-written scanner
+    assert_eq!(term_len("1d6+2"), Some(3));
+    assert_eq!(term_len("+12d20kh3>=15x"), Some(13));
+    assert_eq!(term_len("1d%open5!pro<=3min2max5kl2>7"), Some(28));
+    assert_eq!(term_len("3D6R1"), Some(5));
+    assert_eq!(term_len("1d6ro3"), Some(3));
+    assert_eq!(term_len("1d6r"), Some(3));
+    assert_eq!(term_len("1d6kh"), Some(5));
+    assert_eq!(term_len("1d6!!!"), Some(5));
+    assert_eq!(term_len("4dF"), Some(3));
+    assert_eq!(term_len("2d[1,-2,3]"), Some(10));
+    assert_eq!(term_len("2d[1,]"), Some(1));
+    assert_eq!(term_len("-d"), None);
+    assert_eq!(term_len("+"), None);
+    assert_eq!(term_len("chickens"), None);
+}
+
+#[test]
+fn probability_at_least_a_target() {
+    assert_eq!(probability_at_least("1d20", 11).unwrap(), 0.5);
+    assert_eq!(probability_at_least("1d6", 7).unwrap(), 0.0);
+    assert_eq!(probability_at_least("1d6", 1).unwrap(), 1.0);
+    assert!((probability_at_least("2d6", 7).unwrap() - 21.0 / 36.0).abs() < 1e-9);
+    assert!((probability_at_least("1d20 + 7", 15).unwrap() - 0.65).abs() < 1e-9);
+
+    // exploding dice have no exact distribution, so the chance is sampled
+    let p = probability_at_least("1d6!", 7).unwrap();
+    assert!((p - 1.0 / 6.0).abs() < 0.01);
+    assert!(probability_at_least("1d6 * 2", 7).is_err());
+}
+
+#[test]
+fn probabilities_of_every_total() {
+    let p = probabilities("2d6").unwrap();
+    assert_eq!(p.keys().cloned().collect::<Vec<_>>(), (2..=12).collect::<Vec<_>>());
+    for (&total, &chance) in &p {
+        let ways = 6 - (total - 7).abs();
+        assert!((chance - ways as f64 / 36.0).abs() < 1e-9);
+    }
+    assert!((p.values().sum::<f64>() - 1.0).abs() < 1e-9);
+
+    assert_eq!(probabilities("1d4 - 1d4").unwrap().len(), 7);
+    assert_eq!(probabilities("40d100kh3").unwrap_err(), RollError::TooLarge);
+    assert!(probabilities("1d6!").is_err());
+}
+
+#[test]
+fn average_of_many_rolls() {
+    for n in [1, 2, 7, 1000] {
+        assert_eq!(average("1d1", n).unwrap(), 1.0);
+    }
+    assert!((average("1d6", 100_000).unwrap() - 3.5).abs() < 0.05);
+    assert_eq!(
+        average("1d6", 0).unwrap_err(),
+        RollError::InvalidArgument("n must be at least 1 to average rolls")
+    );
+    assert!(average("1d0", 5).is_err());
+}
+
+#[test]
+fn iterator_yields_failed_rolls_as_errors() {
+    let rolls: Vec<_> = RollExpr::parse("1d6 + 2").unwrap().into_iter().take(25).collect();
+    assert_eq!(rolls.len(), 25);
+    assert!(rolls.iter().all(|r| r.as_ref().is_ok_and(|r| r.total >= 3 && r.total <= 8)));
+
+    // every roll divides by zero, but the iteration still does not end early
+    let rolls: Vec<_> = RollExpr::parse("1d6/(1d1-1)").unwrap().into_iter().take(5).collect();
+    assert_eq!(rolls, vec![Err(RollError::DivisionByZero); 5]);
+}
+
+#[test]
+fn iterator_rolls_cached_terms() {
+    let rolls: Vec<Roll> = roll_dice("3d6 + 2").unwrap().into_iter().take(1000).collect::<Result<_, _>>().unwrap();
+    assert_eq!(rolls.len(), 1000);
+    assert!(rolls.iter().all(|r| r.total >= 5 && r.total <= 20 && r.drex == "3d6+2"));
+
+    // the terms are rolled as they are, so an expression that no longer parses still rolls
+    let mut r = roll_dice("1d1 + 1").unwrap();
+    r.drex = "not an expression".to_string();
+    assert!(r.into_iter().take(1000).all(|r| r.unwrap().total == 2));
+}
+
+#[test]
+fn minimum_floors_each_die_after_its_penalty() {
+    // the second roll of a penetrating d2 that came up 1 counts one less, as 0
+    let values = vec![2, 1];
+    let term = DieRollTerm::parse("1d2!p");
+    assert_eq!(DieRollTerm::calculate(&(term.clone(), values.clone())), 2);
+    assert_eq!(term.format_values(&values), "[2→1↓0]");
+
+    let floored = DieRollTerm::parse("1d2!pmin1");
+    assert_eq!(DieRollTerm::calculate(&(floored.clone(), values.clone())), 3);
+    assert_eq!(floored.faces(&values), vec![2, 1]);
+
+    // the floor lifts every penalized roll of a 1 back to 1, so the mean is 1.5 + 1
+    assert!((mean("1d2!pmin1").unwrap() - 2.5).abs() < 1e-9);
+    assert!((average("1d2!pmin1", 100_000).unwrap() - 2.5).abs() < 0.05);
+    assert!((mean("1d6!pmax4").unwrap() - average("1d6!pmax4", 100_000).unwrap()).abs() < 0.05);
+}
+
+#[test]
+fn dice_are_kept_after_their_minimum_and_maximum() {
+    // raised to the minimum, the 1 and the 2 both count as 3, so the first of them is kept
+    // even though the 2 showed higher
+    let t = DieRollTerm::parse("2d6min3kh1");
+    assert_eq!(t.format_values(&[1, 2]), "[1↑3, (2↑3)]");
+    assert_eq!(DieRollTerm::calculate(&(t.clone(), vec![1, 2])), 3);
+
+    // lowered to the maximum, the 8 ties with the 6 instead of beating it
+    let t = DieRollTerm::parse("2d8max6kh1");
+    assert_eq!(t.format_values(&[6, 8]), "[6, (8↓6)]");
+    assert_eq!(DieRollTerm::calculate(&(t, vec![6, 8])), 6);
+}
+
+#[test]
+fn modifiers_above_a_byte() {
+    assert_eq!(roll_dice("1d1 + 128").unwrap().total, 129);
+    assert_eq!(roll_dice("1d1 * 200").unwrap().total, 200);
+    assert_eq!(roll_dice("1000 - 2d1").unwrap().total, 998);
+    assert_eq!(RollBuilder::new().die(1, 1).modifier(-300).build().unwrap().roll().unwrap().total, -299);
+    assert!(d20_check(150, 10).roll.total >= 151);
+    assert_eq!(roll_dice("1d1 + 99999999999").unwrap_err(), RollError::Overflow);
+}
+
+#[test]
+fn analysis_totals_beyond_i32() {
+    let big = i64::from(i32::MAX);
+    assert_eq!(roll_dice("2147483647+1").unwrap().total, big + 1);
+    assert_eq!(probabilities("2147483647+1").unwrap().into_iter().collect::<Vec<_>>(), vec![(big + 1, 1.0)]);
+
+    let summary = exact_summary("2147483647+1d6").unwrap();
+    assert_eq!((summary.min, summary.max), (big + 1, big + 6));
+    assert!((summary.mean - (big as f64 + 3.5)).abs() < 1e-3);
+
+    assert_eq!(probability_at_least("2000000000+2000000000", 0).unwrap(), 1.0);
+    assert_eq!(probability_at_least("-2000000000-2000000000", 0).unwrap(), 0.0);
+    assert!(!are_equivalent("2147483647+1", "1").unwrap());
+    assert!(are_equivalent("2147483647+1", "1+2147483647").unwrap());
+}
+
+#[test]
+fn custom_faces_beyond_i32_sums() {
+    assert_eq!(roll_dice("2d[2000000000]").unwrap().total, 4_000_000_000);
+    assert_eq!(probabilities("2d[2000000000]").unwrap().into_iter().collect::<Vec<_>>(), vec![(4_000_000_000, 1.0)]);
+
+    let summary = exact_summary("-1d[-2147483648]").unwrap();
+    assert_eq!((summary.min, summary.max), (2_147_483_648, 2_147_483_648));
+    assert_eq!(roll_dice("-1d[-2147483648]").unwrap().total, summary.min);
+}
@@ -0,0 +1,29 @@
+//! Bindings for calling the crate from JavaScript through `wasm-bindgen`, enabled by the `wasm`
+//! feature.
+
+use rand::{Isaac64Rng, SeedableRng};
+use serde::Serialize;
+use serde_wasm_bindgen::Serializer;
+use wasm_bindgen::prelude::*;
+
+use roll_dice_with;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Math)]
+    fn random() -> f64;
+}
+
+/// Rolls a die roll expression, returning the roll as a plain JavaScript object with the same
+/// fields a `Roll` serializes to, or the error message as a string when the expression is bad.
+///
+/// The dice are drawn from a generator seeded by `Math.random()`, as the operating system's
+/// generator that `roll_dice()` relies on is not available in the browser.
+#[wasm_bindgen]
+pub fn roll_js(expr: &str) -> Result<JsValue, JsValue> {
+    let seed = (random() * (1u64 << 53) as f64) as u64;
+    let roll = roll_dice_with(expr, &mut Isaac64Rng::from_seed(&[seed][..]))
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    roll.serialize(&Serializer::json_compatible())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
@@ -0,0 +1,48 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn d20(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_d20")).args(args).output().unwrap()
+}
+
+#[test]
+fn prints_the_roll_of_an_expression() {
+    let out = d20(&["2d1", "+", "3"]);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "2d1[1, 1]+3 (Total: 5)\n");
+
+    let out = d20(&["--count", "3", "1d1+1"]);
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "1d1[1]+1 (Total: 2)\n".repeat(3));
+}
+
+#[test]
+fn reads_the_expression_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_d20"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"1d1-3\n").unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "1d1[1]-3 (Total: -2)\n");
+}
+
+#[test]
+fn seeded_rolls_repeat() {
+    let first = d20(&["--seed", "42", "--count", "5", "4d6kh3"]);
+    let again = d20(&["--seed", "42", "--count", "5", "4d6kh3"]);
+    assert!(first.status.success());
+    assert_eq!(first.stdout, again.stdout);
+    assert_eq!(String::from_utf8_lossy(&first.stdout).lines().count(), 5);
+}
+
+#[test]
+fn bad_expressions_exit_with_1() {
+    let out = d20(&["roll four chickens"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(out.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&out.stderr).starts_with("d20: Invalid die roll expression"));
+
+    assert_eq!(d20(&["--count", "lots", "1d6"]).status.code(), Some(2));
+}
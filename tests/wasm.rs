@@ -0,0 +1,21 @@
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+extern crate d20;
+extern crate js_sys;
+extern crate wasm_bindgen;
+extern crate wasm_bindgen_test;
+
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+fn rolls_cross_into_javascript() {
+    let roll = d20::roll_js("2d1 + 3").unwrap();
+    let total = js_sys::Reflect::get(&roll, &JsValue::from_str("total")).unwrap();
+    assert_eq!(total.as_f64(), Some(5.0));
+
+    let err = d20::roll_js("roll four chickens").unwrap_err();
+    assert!(err.as_string().unwrap().starts_with("Invalid die roll expression"));
+}